@@ -0,0 +1,86 @@
+// Legal neighbourhoods of a hat, read off a substitution tiling.
+//
+// A neighbourhood (the tile's corona) is every hat that shares at least one
+// vertex with it, given relative to the tile, i.e. as if the tile were the
+// unreflected hat at the origin.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::lattice::Eisenstein;
+use crate::metatile::{self, Metatile};
+use crate::patch::Patch;
+use crate::tile::HatPose;
+
+// Where other sits as seen from tile
+pub fn relative(tile: &HatPose, other: &HatPose) -> HatPose {
+    tile.isometry().inverse().apply_to_pose(other)
+}
+
+// For every tile of a patch, the other tiles that share a vertex with it
+pub fn touching(tiles: &[HatPose]) -> Vec<Vec<usize>> {
+    let mut at_vertex: HashMap<Eisenstein, Vec<usize>> = HashMap::new();
+    for (i, t) in tiles.iter().enumerate() {
+        for v in t.vertices().iter() {
+            at_vertex.entry(*v).or_default().push(i);
+        }
+    }
+    tiles.iter().enumerate().map(|(i, t)| {
+        let mut n: Vec<usize> = t.vertices().iter()
+            .flat_map(|v| at_vertex[v].iter().copied())
+            .filter(|&j| j != i)
+            .collect();
+        n.sort_unstable();
+        n.dedup();
+        n
+    }).collect()
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Atlas {
+    // Sorted neighbourhoods, each sorted
+    pub coronas: Vec<Vec<HatPose>>,
+}
+
+impl Atlas {
+    // The complete neighbourhoods found in a legal patch. Tiles with a vertex
+    // on the patch boundary are skipped, as their neighbourhood may be cut short.
+    pub fn from_patch(patch: &Patch) -> Atlas {
+        let boundary: std::collections::HashSet<Eisenstein> = patch.boundary_edges().iter()
+            .flat_map(|e| vec![e.start, e.end])
+            .collect();
+        let mut coronas: Vec<Vec<HatPose>> = touching(&patch.tiles).iter().enumerate()
+            .filter(|(i, _)| !patch.tiles[*i].vertices().iter().any(|v| boundary.contains(v)))
+            .map(|(i, n)| {
+                let t = &patch.tiles[i];
+                let mut c: Vec<HatPose> = n.iter().map(|&j| relative(t, &patch.tiles[j])).collect();
+                c.sort_unstable();
+                c
+            })
+            .collect();
+        coronas.sort_unstable();
+        coronas.dedup();
+        Atlas { coronas }
+    }
+
+    // Every neighbourhood of the hat tiling, from a level 4 H supertile.
+    // Built once and shared.
+    pub fn standard() -> &'static Atlas {
+        static STANDARD: OnceLock<Atlas> = OnceLock::new();
+        STANDARD.get_or_init(|| Atlas::from_patch(&Patch::new(metatile::supertile(Metatile::H, 4))))
+    }
+
+    pub fn len(&self) -> usize {
+        self.coronas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coronas.is_empty()
+    }
+
+    // Whether some legal neighbourhood includes all these neighbours, given
+    // relative to the tile
+    pub fn fits(&self, neighbours: &[HatPose]) -> bool {
+        self.coronas.iter().any(|c| neighbours.iter().all(|n| c.binary_search(n).is_ok()))
+    }
+}
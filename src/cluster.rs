@@ -0,0 +1,93 @@
+// Connected clusters of hats as they occur in a patch, counted up to
+// rotation, reflection and translation
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::patch::Patch;
+use crate::tile::{self, HatPose};
+
+// One kind of cluster: its tiles in canonical form, how often it occurs and
+// what fraction of all clusters of its size that is
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cluster {
+    pub tiles: Vec<HatPose>,
+    pub count: usize,
+    pub frequency: f64,
+}
+
+// Every connected cluster of k tiles of the patch, tiles being connected if
+// they share an edge, counted by shape. Clusters are grown one hat at a
+// time from the hats of the patch attached to their edges, so only clusters
+// wholly inside the patch are seen. Most common first.
+pub fn clusters(patch: &Patch, k: usize) -> Vec<Cluster> {
+    if k == 0 {
+        return Vec::new();
+    }
+    // Tiles attached to each tile along an edge
+    let index: HashMap<HatPose, usize> = patch.tiles.iter().enumerate().map(|(i, t)| (*t, i)).collect();
+    let attached: Vec<Vec<usize>> = patch.tiles.iter().map(|t| {
+        t.get_edges().iter()
+            .flat_map(|e| [false, true].iter().flat_map(move |r| tile::poses_on_edge(e, *r)))
+            .filter_map(|pose| index.get(&pose).copied())
+            .collect()
+    }).collect();
+
+    let mut level: HashSet<Vec<usize>> = (0..patch.len()).map(|i| vec![i]).collect();
+    for _ in 1..k {
+        let mut next = HashSet::new();
+        for c in &level {
+            for &j in c.iter().flat_map(|&i| attached[i].iter()) {
+                if !c.contains(&j) {
+                    let mut bigger = c.clone();
+                    bigger.push(j);
+                    bigger.sort_unstable();
+                    next.insert(bigger);
+                }
+            }
+        }
+        level = next;
+    }
+
+    let mut counts: BTreeMap<Vec<HatPose>, usize> = BTreeMap::new();
+    for c in &level {
+        let tiles: Vec<HatPose> = c.iter().map(|&i| patch.tiles[i]).collect();
+        *counts.entry(Patch::new(tiles).canonical().tiles).or_default() += 1;
+    }
+    let total = level.len() as f64;
+    let mut result: Vec<Cluster> = counts.into_iter()
+        .map(|(tiles, count)| Cluster { tiles, count, frequency: count as f64 / total })
+        .collect();
+    result.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tiles.cmp(&b.tiles)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metatile::{self, Metatile};
+
+    #[test]
+    fn cluster_classes() {
+        let patch = Patch::new(metatile::supertile(Metatile::H, 4));
+        let singles = clusters(&patch, 1);
+        assert_eq!(singles.len(), 1);
+        assert_eq!(singles[0].count, patch.len());
+        assert!(clusters(&patch, 0).is_empty());
+
+        let pairs = clusters(&patch, 2);
+        assert_eq!(pairs.len(), 13);
+        for k in 2..=3 {
+            let found = if k == 2 { pairs.clone() } else { clusters(&patch, k) };
+            assert!((found.iter().map(|c| c.frequency).sum::<f64>() - 1.).abs() < 1e-9);
+            assert!(found.windows(2).all(|w| w[0].count >= w[1].count));
+            for c in &found {
+                let cluster = Patch::new(c.tiles.clone());
+                assert_eq!(cluster.len(), k);
+                assert!(cluster.overlapping_pairs().is_empty());
+                assert_eq!(cluster.outline().len(), 1);
+                // Connected: the cluster is a cluster of itself
+                assert_eq!(clusters(&cluster, k).len(), 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Recovering the supertiles of a patch of hats, the inverse of substitution.
+//
+// A patch usually fits into a supertile (see legality) in several ways, and
+// they need not group its hats the same way. A supertile of the hierarchy is
+// only kept if its hats all lie in the patch and it is the same, down to
+// every hat, under every placement of the patch in the supertile searched.
+// Those are the supertiles the patch itself forces.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::hierarchy::{Hierarchy, Supertile};
+use crate::legality;
+use crate::metatile::{self, Metatile};
+use crate::patch::Patch;
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Deflation {
+    // Forced supertiles of every level, lowest first. The children of a
+    // metatile are tiles of the patch, otherwise supertiles of this list.
+    // A parent is only given if it too is forced.
+    pub supertiles: Vec<Supertile>,
+    // Tiles of the patch in no forced metatile
+    pub leftovers: Vec<usize>,
+}
+
+impl Deflation {
+    pub fn levels(&self) -> u32 {
+        self.supertiles.iter().map(|s| s.level).max().unwrap_or(0)
+    }
+
+    // The forced supertiles of one level
+    pub fn at_level(&self, level: u32) -> Vec<usize> {
+        (0..self.supertiles.len()).filter(|&s| self.supertiles[s].level == level).collect()
+    }
+}
+
+// A supertile in terms of the patch: its kind, its level and its children,
+// sorted. The children of a metatile are tiles of the patch, otherwise they
+// are the numbers of other keys.
+type Key = (Metatile, u32, Vec<usize>);
+
+// Numbers keys, so that a supertile found under two placements gets the same
+// number both times
+#[derive(Debug, Default)]
+struct Keys {
+    ids: HashMap<Key, usize>,
+    keys: Vec<Key>,
+}
+
+impl Keys {
+    fn id(&mut self, key: Key) -> usize {
+        if let Some(&id) = self.ids.get(&key) {
+            return id;
+        }
+        self.keys.push(key.clone());
+        self.ids.insert(key, self.keys.len() - 1);
+        self.keys.len() - 1
+    }
+}
+
+// The supertiles of h all of whose hats are tiles of the patch, given the
+// patch tile on each tile of h, as key numbers, lowest level first
+fn complete(h: &Hierarchy, tile_of: &HashMap<usize, usize>, keys: &mut Keys) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..h.supertiles.len()).collect();
+    order.sort_by_key(|&s| h.supertiles[s].level);
+    // Supertiles of h that are complete, and their key numbers
+    let mut found: HashMap<usize, usize> = HashMap::new();
+    let mut result = Vec::new();
+    for s in order {
+        let st = &h.supertiles[s];
+        let below = if st.level == 1 { tile_of } else { &found };
+        let children: Option<Vec<usize>> = st.children.iter().map(|c| below.get(c).copied()).collect();
+        if let Some(mut children) = children {
+            children.sort_unstable();
+            let id = keys.id((st.kind, st.level, children));
+            found.insert(s, id);
+            result.push(id);
+        }
+    }
+    result
+}
+
+// The supertiles of a patch, or as for legality::check the first tile that
+// cannot be part of a hat tiling
+pub fn deflate(patch: &Patch) -> Result<Deflation, usize> {
+    let e = legality::embed(patch)?;
+    let h = metatile::hierarchy(Metatile::H, e.level);
+    let mut keys = Keys::default();
+    let mut forced: Option<Vec<usize>> = None;
+    for iso in e.placements() {
+        let tile_of: HashMap<usize, usize> = patch.tiles.iter().enumerate()
+            .filter_map(|(i, t)| e.tile_at(&iso.apply_to_pose(t)).map(|s| (s, i)))
+            .collect();
+        let found = complete(&h, &tile_of, &mut keys);
+        forced = Some(match forced {
+            Some(f) => {
+                let found: HashSet<usize> = found.into_iter().collect();
+                f.into_iter().filter(|id| found.contains(id)).collect()
+            },
+            None => found,
+        });
+        if forced.as_ref().is_some_and(|f| f.is_empty()) {
+            break;
+        }
+    }
+
+    let mut result = Deflation::default();
+    // Key numbers of forced supertiles, and their index in the result
+    let mut index: HashMap<usize, usize> = HashMap::new();
+    for id in forced.unwrap_or_default() {
+        let (kind, level, children) = keys.keys[id].clone();
+        let id_in_result = result.supertiles.len();
+        let children = if level == 1 {
+            children
+        } else {
+            // Every child of a forced supertile is itself forced
+            let children: Vec<usize> = children.iter().filter_map(|c| index.get(c).copied()).collect();
+            for &c in &children {
+                result.supertiles[c].parent = Some(id_in_result);
+            }
+            children
+        };
+        index.insert(id, id_in_result);
+        result.supertiles.push(Supertile { kind, level, parent: None, children });
+    }
+
+    let mut grouped = vec![false; patch.len()];
+    for st in result.supertiles.iter().filter(|s| s.level == 1) {
+        for &c in &st.children {
+            grouped[c] = true;
+        }
+    }
+    result.leftovers = (0..patch.len()).filter(|&i| !grouped[i]).collect();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grow::grow;
+
+    // Whether every metatile found is complete, and of the same kind, under
+    // every placement of the patch
+    fn forced_everywhere(patch: &Patch, d: &Deflation) -> bool {
+        let e = legality::embed(patch).unwrap();
+        let h = metatile::hierarchy(Metatile::H, e.level);
+        e.placements().iter().all(|iso| {
+            d.at_level(1).iter().all(|&s| {
+                let st = &d.supertiles[s];
+                let on: Vec<usize> = st.children.iter()
+                    .filter_map(|&c| e.tile_at(&iso.apply_to_pose(&patch.tiles[c])))
+                    .collect();
+                on.len() == st.children.len() && on.iter().all(|&t| {
+                    let p = h.tile_parents[t];
+                    h.supertiles[p].kind == st.kind && h.children(p).len() == on.len()
+                        && h.children(p).iter().all(|c| on.contains(c))
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn ambiguous_patches() {
+        // The first placement of these finds 3 and 7 complete metatiles
+        for (n, seed, metatiles) in [(10, 4, 1), (20, 1, 3)] {
+            let patch = grow(&Patch::default(), n, seed).unwrap();
+            let d = deflate(&patch).unwrap();
+            assert_eq!(d.at_level(1).len(), metatiles);
+            assert!(forced_everywhere(&patch, &d));
+        }
+    }
+
+    #[test]
+    fn repeated_tiles() {
+        let hat = crate::tile::HatPose::default();
+        assert_eq!(deflate(&Patch::new(vec![hat, hat])), Err(1));
+        let mut tiles = metatile::supertile(Metatile::H, 2);
+        tiles.push(tiles[3]);
+        assert_eq!(deflate(&Patch::new(tiles)), Err(25));
+    }
+
+    #[test]
+    fn whole_supertile() {
+        let d = deflate(&Patch::new(metatile::supertile(Metatile::H, 3))).unwrap();
+        let counts: Vec<usize> = (1..=d.levels()).map(|l| d.at_level(l).len()).collect();
+        assert_eq!(counts, [64, 10, 1]);
+        assert!(d.leftovers.is_empty());
+    }
+}
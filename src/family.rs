@@ -0,0 +1,180 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use crate::lattice::Vertex;
+use crate::polygon::Point;
+use crate::tile::{Edge, EdgeLength, HatPose};
+
+// Tile(a, b) from the paper: the hat outline with every Short edge given
+// length a and every Long edge length b, keeping all the edge directions.
+// The hat itself is Tile(1, sqrt(3)).
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct TileShape {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl TileShape {
+    pub fn new(a: f64, b: f64) -> Self {
+        Self { a, b }
+    }
+
+    pub fn hat() -> Self {
+        TileShape::new(1., 3_f64.sqrt())
+    }
+
+    pub fn turtle() -> Self {
+        TileShape::new(3_f64.sqrt(), 1.)
+    }
+
+    pub fn chevron() -> Self {
+        TileShape::new(0., 1.)
+    }
+
+    pub fn comet() -> Self {
+        TileShape::new(1., 0.)
+    }
+
+    // Tile(1, 1), the equilateral polygon underlying the spectre
+    pub fn equilateral() -> Self {
+        TileShape::new(1., 1.)
+    }
+
+    pub fn name(&self) -> String {
+        let named = [(TileShape::hat(), "hat"),
+                     (TileShape::turtle(), "turtle"),
+                     (TileShape::chevron(), "chevron"),
+                     (TileShape::comet(), "comet"),
+                     (TileShape::equilateral(), "Tile(1,1)")];
+        match named.iter().find(|(s, _)| s == self) {
+            Some((_, n)) => n.to_string(),
+            None => format!("Tile({},{})", self.a, self.b),
+        }
+    }
+
+    // Real length of an edge, in the units of the hat's geometry
+    pub fn edge_length(&self, length: EdgeLength) -> f64 {
+        self.scale(length) * match length {
+            EdgeLength::Short => 1.,
+            EdgeLength::Long => 3_f64.sqrt(),
+        } * crate::lattice::UNIT
+    }
+
+    // Length and direction in degrees of each edge of the unreflected tile
+    pub fn edges(&self) -> Vec<(f64, i32)> {
+        HatPose::default().get_edges().iter()
+            .map(|e| (self.edge_length(e.length), e.angle()))
+            .collect()
+    }
+
+    // The tile in the given pose, on its own. Neighbouring tiles only line
+    // up for the hat; use deform for a patch.
+    pub fn outline(&self, pose: &HatPose) -> Vec<Point> {
+        deform(&[pose.get_edges()], self).pop().unwrap_or_default()
+    }
+
+    pub fn area(&self) -> f64 {
+        crate::polygon::area(&self.outline(&HatPose::default()))
+    }
+
+    // How much a hat edge vector is stretched
+    fn scale(&self, length: EdgeLength) -> f64 {
+        match length {
+            EdgeLength::Short => self.a,
+            EdgeLength::Long => self.b / 3_f64.sqrt(),
+        }
+    }
+
+    fn step<P: Vertex>(&self, from: Point, v: P, length: EdgeLength) -> Point {
+        let d = v.to_point();
+        let s = self.scale(length);
+        (from.0 + s*d.0, from.1 + s*d.1)
+    }
+}
+
+// Outlines of a patch of hats, given by the edges of each tile, redrawn as
+// Tile(a, b). Vertex positions are found by walking the edges shared between
+// tiles, so neighbouring tiles stay edge to edge. Each connected group of
+// tiles keeps one vertex where the hats put it.
+pub fn deform<P: Vertex>(tiles: &[Vec<Edge<P>>], shape: &TileShape) -> Vec<Vec<Point>> {
+    let mut adjacent: HashMap<P, Vec<(P, EdgeLength)>> = HashMap::new();
+    for e in tiles.iter().flatten() {
+        adjacent.entry(e.start).or_default().push((e.end, e.length));
+        adjacent.entry(e.end).or_default().push((e.start, e.length));
+    }
+
+    let mut pos: HashMap<P, Point> = HashMap::new();
+    for e in tiles.iter().flatten() {
+        if pos.contains_key(&e.start) {
+            continue;
+        }
+        pos.insert(e.start, e.start.to_point());
+        let mut queue = VecDeque::from(vec![e.start]);
+        while let Some(v) = queue.pop_front() {
+            let p = pos[&v];
+            for &(w, length) in &adjacent[&v] {
+                if let Entry::Vacant(slot) = pos.entry(w) {
+                    slot.insert(shape.step(p, w - v, length));
+                    queue.push_back(w);
+                }
+            }
+        }
+    }
+
+    tiles.iter()
+        .map(|edges| edges_in_order(edges).iter().map(|e| pos[&e.start]).collect())
+        .collect()
+}
+
+// Edges of one tile chained end to start, whatever order they were listed in
+fn edges_in_order<P: Vertex>(edges: &[Edge<P>]) -> Vec<Edge<P>> {
+    let mut result: Vec<Edge<P>> = edges.iter().take(1).cloned().collect();
+    while result.len() < edges.len() {
+        let last = result[result.len() - 1].end;
+        match edges.iter().find(|e| e.start == last) {
+            Some(e) => result.push(*e),
+            None => break,
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metatile::{self, Metatile};
+
+    fn close(p: Point, q: Point) -> bool {
+        (p.0 - q.0).hypot(p.1 - q.1) < 1e-9
+    }
+
+    #[test]
+    fn hat_shape_keeps_hats() {
+        let tiles = metatile::supertile(Metatile::H, 2);
+        let edges: Vec<_> = tiles.iter().map(|t| t.get_edges()).collect();
+        let outlines = deform(&edges, &TileShape::hat());
+        for (t, outline) in tiles.iter().zip(&outlines) {
+            let points = t.points();
+            assert_eq!(outline.len(), points.len());
+            assert!(outline.iter().all(|p| points.iter().any(|q| close(*p, *q))));
+        }
+    }
+
+    #[test]
+    fn degenerate_shapes_stay_closed() {
+        let tiles = metatile::supertile(Metatile::H, 2);
+        let edges: Vec<_> = tiles.iter().map(|t| t.get_edges()).collect();
+        for shape in [TileShape::chevron(), TileShape::comet()].iter() {
+            let outlines = deform(&edges, shape);
+            for (e, outline) in edges.iter().zip(&outlines) {
+                let ordered = edges_in_order(e);
+                assert_eq!(ordered.len(), outline.len());
+                for (i, edge) in ordered.iter().enumerate() {
+                    let (p, q) = (outline[i], outline[(i + 1) % outline.len()]);
+                    let length = (q.0 - p.0).hypot(q.1 - p.1);
+                    assert!((length - shape.edge_length(edge.length)).abs() < 1e-9);
+                }
+            }
+        }
+    }
+}
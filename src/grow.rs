@@ -0,0 +1,361 @@
+// Growing hat patches one legal tile at a time
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::atlas::{self, Atlas};
+use crate::lattice::{Eisenstein, Hex, Kite};
+use crate::legality::{self, Embedding};
+use crate::patch::Patch;
+use crate::random::Rng;
+use crate::tile::{self, Edge, HatPose};
+
+// A patch being built, with its boundary edges, covered kites and vertices
+// kept up to date as tiles come and go. New tiles must keep every
+// neighbourhood within the atlas. That alone lets through patches that
+// cannot be extended to a tiling, so given an embedding they must also keep
+// the patch within its supertile.
+#[derive(Debug, Clone)]
+pub struct Frontier<'a> {
+    pub tiles: Vec<HatPose>,
+    atlas: &'a Atlas,
+    embedding: Option<Embedding>,
+    covered: HashSet<Kite>,
+    boundary: BTreeSet<Edge>,
+    at_vertex: HashMap<Eisenstein, Vec<usize>>,
+    // Each tile's neighbours so far, as seen from the tile
+    near: Vec<Vec<HatPose>>,
+}
+
+impl<'a> Frontier<'a> {
+    // The embedding, if any, must already hold the patch's tiles, as one
+    // from legality::embed does
+    pub fn new(patch: &Patch, atlas: &'a Atlas, embedding: Option<Embedding>) -> Self {
+        let mut f = Frontier {
+            tiles: Vec::new(),
+            atlas,
+            embedding: None,
+            covered: HashSet::new(),
+            boundary: BTreeSet::new(),
+            at_vertex: HashMap::new(),
+            near: Vec::new(),
+        };
+        for t in &patch.tiles {
+            f.push(*t);
+        }
+        f.embedding = embedding;
+        f
+    }
+
+    pub fn push(&mut self, pose: HatPose) {
+        for e in pose.get_edges() {
+            if !self.boundary.remove(&e.reversed()) {
+                self.boundary.insert(e);
+            }
+        }
+        self.covered.extend(pose.kites().iter());
+        let touching = self.touching(&pose);
+        for &i in &touching {
+            let rel = atlas::relative(&self.tiles[i], &pose);
+            self.near[i].push(rel);
+        }
+        self.near.push(touching.iter().map(|&i| atlas::relative(&pose, &self.tiles[i])).collect());
+        for v in pose.vertices().iter() {
+            self.at_vertex.entry(*v).or_default().push(self.tiles.len());
+        }
+        if let Some(e) = self.embedding.as_mut() {
+            e.push(&pose);
+        }
+        self.tiles.push(pose);
+    }
+
+    pub fn pop(&mut self) -> Option<HatPose> {
+        let pose = self.tiles.pop()?;
+        for e in pose.get_edges() {
+            if !self.boundary.remove(&e) {
+                self.boundary.insert(e.reversed());
+            }
+        }
+        for k in pose.kites().iter() {
+            self.covered.remove(k);
+        }
+        for v in pose.vertices().iter() {
+            if let Some(at) = self.at_vertex.get_mut(v) {
+                at.pop();
+            }
+        }
+        self.near.pop();
+        for i in self.touching(&pose) {
+            self.near[i].pop();
+        }
+        if let Some(e) = self.embedding.as_mut() {
+            e.pop();
+        }
+        Some(pose)
+    }
+
+    // In a fixed order, so that runs are reproducible
+    pub fn boundary_edges(&self) -> Vec<Edge> {
+        self.boundary.iter().copied().collect()
+    }
+
+    // Tiles sharing a vertex with a pose that is not in the patch
+    fn touching(&self, pose: &HatPose) -> Vec<usize> {
+        let mut n: Vec<usize> = pose.vertices().iter()
+            .flat_map(|v| self.at_vertex.get(v).into_iter().flatten().copied())
+            .collect();
+        n.sort_unstable();
+        n.dedup();
+        n
+    }
+
+    // Whether pose can join the patch without overlapping it or leaving any
+    // neighbourhood, its own included, outside the atlas
+    fn fits_atlas(&self, pose: &HatPose) -> bool {
+        if pose.kites().iter().any(|k| self.covered.contains(k)) {
+            return false;
+        }
+        let near = self.touching(pose);
+        let own: Vec<HatPose> = near.iter().map(|&i| atlas::relative(pose, &self.tiles[i])).collect();
+        if !self.atlas.fits(&own) {
+            return false;
+        }
+        near.iter().all(|&i| {
+            let mut theirs = self.near[i].clone();
+            theirs.push(atlas::relative(&self.tiles[i], pose));
+            self.atlas.fits(&theirs)
+        })
+    }
+
+    fn fits_embedding(&self, pose: &HatPose) -> bool {
+        self.embedding.iter().all(|e| e.fits(pose))
+    }
+
+    pub fn is_legal(&self, pose: &HatPose) -> bool {
+        self.fits_atlas(pose) && self.fits_embedding(pose)
+    }
+
+    fn atlas_completions(&self, edge: &Edge) -> Vec<HatPose> {
+        [false, true].iter()
+            .flat_map(|r| tile::poses_on_edge(edge, *r))
+            .filter(|p| self.fits_atlas(p))
+            .collect()
+    }
+
+    // Every legal hat, of either chirality, on the far side of a boundary edge
+    pub fn completions(&self, edge: &Edge) -> Vec<HatPose> {
+        self.atlas_completions(edge).into_iter().filter(|p| self.fits_embedding(p)).collect()
+    }
+
+    pub fn into_patch(self) -> Patch {
+        Patch::new(self.tiles)
+    }
+}
+
+// Completions of boundary edges allowed by the atlas, recomputed only near
+// tiles that change. The embedding depends on the whole patch, so is checked
+// afresh every time.
+#[derive(Debug, Clone, Default)]
+struct Cache {
+    completions: HashMap<Edge, Vec<HatPose>>,
+}
+
+impl Cache {
+    fn get(&mut self, f: &Frontier, e: &Edge) -> Vec<HatPose> {
+        self.completions.entry(*e).or_insert_with(|| f.atlas_completions(e))
+            .iter().copied()
+            .filter(|p| f.fits_embedding(p))
+            .collect()
+    }
+
+    // Drop every edge whose completions may depend on a tile in hexagon h.
+    // A completion is within 2 hexagons of its edge, tiles that share a
+    // vertex are within 3 of each other, and legality looks at the
+    // completion's neighbours and theirs.
+    fn forget_near(&mut self, h: Hex) {
+        self.completions.retain(|e, _| {
+            let c = e.center();
+            Hex::from_point(c.0, c.1).distance(h) > 2 + 3 + 3
+        });
+    }
+}
+
+// The completions of the boundary edge to fill next: a forced one if there
+// is one, otherwise a random edge. None if some edge has no completion at
+// all, i.e. the patch has painted itself into a corner.
+fn choose(f: &Frontier, cache: &mut Cache, rng: &mut Rng) -> Option<Vec<HatPose>> {
+    let options: Vec<Vec<HatPose>> = f.boundary_edges().iter().map(|e| cache.get(f, e)).collect();
+    if options.iter().any(|o| o.is_empty()) {
+        return None;
+    }
+    if let Some(o) = options.iter().find(|o| o.len() == 1) {
+        return Some(o.clone());
+    }
+    Some(options[rng.below(options.len())].clone())
+}
+
+// Grow a patch outwards with random legal hats until it has target tiles,
+// backtracking out of dead ends so that no boundary edge is left without a
+// legal neighbour. An empty patch starts from a single hat. The same seed
+// always gives the same patch, and is recorded with it. None if the search
+// runs out of alternatives or steps, or the patch is not legal to begin with.
+pub fn grow(patch: &Patch, target: usize, seed: u64) -> Option<Patch> {
+    let mut rng = Rng::new(seed);
+    let mut embedding = Embedding::new(legality::level_for_tiles(target));
+    embedding.extend(&patch.tiles).ok()?;
+    let mut f = Frontier::new(patch, Atlas::standard(), Some(embedding));
+    if f.tiles.is_empty() {
+        f.push(HatPose::default());
+    }
+
+    // Untried alternatives for each tile placed so far
+    let mut stack: Vec<Vec<HatPose>> = Vec::new();
+    let mut cache = Cache::default();
+    let mut steps = 0;
+    while f.tiles.len() < target {
+        steps += 1;
+        if steps > 50 * target {
+            return None;
+        }
+        match choose(&f, &mut cache, &mut rng) {
+            Some(mut candidates) => {
+                rng.shuffle(&mut candidates);
+                let t = candidates.pop()?;
+                f.push(t);
+                cache.forget_near(t.hex);
+                stack.push(candidates);
+            },
+            None => loop {
+                // Backtracking into the original patch means there is no way out
+                let mut rest = stack.pop()?;
+                let old = f.pop()?;
+                cache.forget_near(old.hex);
+                if let Some(t) = rest.pop() {
+                    f.push(t);
+                    cache.forget_near(t.hex);
+                    stack.push(rest);
+                    break;
+                }
+            },
+        }
+    }
+    Some(f.into_patch().with_seed(Some(seed)))
+}
+
+// What auto-completion did: how many tiles it placed and which boundary
+// edges were left with no legal neighbour at all
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Completion {
+    pub added: usize,
+    pub dead: Vec<Edge>,
+}
+
+// Place every forced hat, i.e. the only legal completion of some boundary
+// edge, until none are left. A patch that cannot occur in a hat tiling is
+// completed by the atlas alone.
+pub fn auto_complete(patch: &Patch) -> (Patch, Completion) {
+    let mut f = Frontier::new(patch, Atlas::standard(), legality::embed(patch).ok());
+    let mut cache = Cache::default();
+    let mut added = 0;
+    loop {
+        let forced = f.boundary_edges().iter()
+            .map(|e| cache.get(&f, e))
+            .find(|o| o.len() == 1);
+        match forced {
+            Some(o) => {
+                f.push(o[0]);
+                cache.forget_near(o[0].hex);
+                added += 1;
+            },
+            None => break,
+        }
+    }
+    let dead = f.boundary_edges().into_iter()
+        .filter(|e| cache.get(&f, e).is_empty())
+        .collect();
+    (f.into_patch().with_seed(patch.seed), Completion { added, dead })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metatile::{self, Metatile};
+
+    #[test]
+    fn grown_patches_are_legal() {
+        for seed in 1..=3 {
+            let patch = grow(&Patch::default(), 50, seed).unwrap();
+            assert_eq!(patch.len(), 50);
+            assert_eq!(patch.seed, Some(seed));
+            assert!(patch.overlapping_pairs().is_empty(), "seed {}", seed);
+            assert!(legality::check(&patch).is_ok(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn seeds_are_reproducible() {
+        let a = grow(&Patch::default(), 30, 7).unwrap();
+        let b = grow(&Patch::default(), 30, 7).unwrap();
+        let c = grow(&Patch::default(), 30, 8).unwrap();
+        assert_eq!(a.to_json(), b.to_json());
+        assert_ne!(a.to_json(), c.to_json());
+        assert_ne!(a.tiles, c.tiles);
+    }
+
+    #[test]
+    fn push_and_pop_restore_the_boundary() {
+        let patch = Patch::new(metatile::supertile(Metatile::H, 2));
+        let mut f = Frontier::new(&patch, Atlas::standard(), legality::embed(&patch).ok());
+        let before = f.boundary_edges();
+        let mut expected = patch.boundary_edges();
+        expected.sort_unstable();
+        assert_eq!(before, expected);
+
+        let edge = before[0];
+        let completions = f.completions(&edge);
+        assert!(!completions.is_empty());
+        for t in completions {
+            f.push(t);
+            assert_ne!(f.boundary_edges(), before);
+            assert_eq!(f.pop(), Some(t));
+            assert_eq!(f.boundary_edges(), before);
+        }
+    }
+
+    #[test]
+    fn auto_complete_restores_a_missing_hat() {
+        let tiles = metatile::supertile(Metatile::H, 3);
+        let full = Patch::new(tiles.clone());
+        let boundary = full.boundary_edges();
+        let inner = tiles.iter()
+            .position(|t| t.get_edges().iter().all(|e| !boundary.contains(e)))
+            .unwrap();
+        let mut holed = full.clone();
+        let missing = holed.tiles.remove(inner);
+
+        // Forced hats outside the supertile are placed either way; the hole
+        // costs exactly one more
+        let (whole, outside) = auto_complete(&full);
+        let (patch, completion) = auto_complete(&holed);
+        assert!(outside.dead.is_empty());
+        assert_eq!(completion, Completion { added: outside.added + 1, dead: Vec::new() });
+        assert!(patch.tiles.contains(&missing));
+        let mut expected = whole.tiles;
+        let mut got = patch.tiles;
+        expected.sort_unstable();
+        got.sort_unstable();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn auto_complete_reports_dead_edges() {
+        use crate::lattice::hex;
+        let patch = Patch::new(vec![HatPose::default(), HatPose::new(hex(2, 0), 0, true)]);
+        assert!(legality::check(&patch).is_err());
+        let (same, completion) = auto_complete(&patch);
+        assert_eq!(same, patch);
+        assert_eq!(completion.added, 0);
+        assert!(!completion.dead.is_empty());
+        let boundary = patch.boundary_edges();
+        assert!(completion.dead.iter().all(|e| boundary.contains(e)));
+    }
+}
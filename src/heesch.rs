@@ -0,0 +1,278 @@
+// Coronas and Heesch numbers.
+//
+// A corona of a set of tiles is a ring of further tiles that surrounds it
+// completely, i.e. leaves it in the interior of their union. The Heesch
+// number of a shape is how many coronas of copies of it can be put around
+// one copy, each corona surrounding everything inside it without holes.
+
+use std::collections::HashSet;
+
+use crate::atlas;
+use crate::lattice::{hex, kite, Eisenstein, Hex, Kite};
+use crate::outline;
+use crate::patch::Patch;
+use crate::polygon;
+use crate::tile::{Edge, EdgeLength, HatPose, Isometry};
+
+// The kites with a corner at v
+pub fn kites_at(v: Eisenstein) -> Vec<Kite> {
+    let (x, y) = v.to_point();
+    let h = Hex::from_point(x, y);
+    let mut result: Vec<Kite> = (-1..=1)
+        .flat_map(|dq| (-1..=1).map(move |dr| h + hex(dq, dr)))
+        .filter(|n| h.distance(*n) <= 1)
+        .flat_map(|n| (0..6).map(move |k| kite(n, k)))
+        .filter(|kt| kt.vertices().contains(&v))
+        .collect();
+    result.sort_unstable();
+    result
+}
+
+// Whether every kite around every corner of inner is covered
+fn surrounds(inner: &HashSet<Kite>, covered: &HashSet<Kite>) -> bool {
+    inner.iter()
+        .flat_map(|kt| kt.vertices())
+        .all(|v| kites_at(v).iter().all(|kt| covered.contains(kt)))
+}
+
+// The first k coronas of a cluster of a patch's tiles, each the tiles not
+// used so far that share a point with those inside it. Stops early at a
+// ring that does not surround the tiles inside, as at the edge of the patch.
+pub fn coronas(patch: &Patch, cluster: &[usize], k: usize) -> Vec<Vec<usize>> {
+    let touching = atlas::touching(&patch.tiles);
+    let mut used: HashSet<usize> = cluster.iter().copied().collect();
+    let mut inner: HashSet<Kite> = cluster.iter().flat_map(|&i| patch.tiles[i].kites()).collect();
+    let mut last: Vec<usize> = cluster.to_vec();
+    let mut result = Vec::new();
+    while result.len() < k {
+        let mut ring: Vec<usize> = last.iter()
+            .flat_map(|&i| touching[i].iter().copied())
+            .filter(|j| !used.contains(j))
+            .collect();
+        ring.sort_unstable();
+        ring.dedup();
+        let mut covered = inner.clone();
+        covered.extend(ring.iter().flat_map(|&i| patch.tiles[i].kites()));
+        if ring.is_empty() || !surrounds(&inner, &covered) {
+            break;
+        }
+        used.extend(ring.iter().copied());
+        inner = covered;
+        last = ring.clone();
+        result.push(ring);
+    }
+    result
+}
+
+// A shape made of kites, such as the hat or any other polykite
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Polykite {
+    pub kites: Vec<Kite>,
+    // Every turned and reflected copy, each sorted, without repeats up to
+    // translation
+    images: Vec<Vec<Kite>>,
+}
+
+impl Polykite {
+    // None for a shape with no kites
+    pub fn new(kites: &[Kite]) -> Option<Self> {
+        let mut images: Vec<Vec<Kite>> = Vec::new();
+        let mut seen = HashSet::new();
+        for reflected in [false, true].iter() {
+            for rotation in 0..6 {
+                let iso = Isometry::new(rotation, *reflected, Hex::default());
+                let mut image: Vec<Kite> = kites.iter().map(|kt| iso.apply_to_kite(*kt)).collect();
+                image.sort_unstable();
+                // Compare copies with their first kite in the same hexagon
+                let base = image.first()?.hex;
+                let key: Vec<Kite> = image.iter().map(|kt| Kite { hex: kt.hex - base, k: kt.k }).collect();
+                if seen.insert(key) {
+                    images.push(image);
+                }
+            }
+        }
+        let mut kites = kites.to_vec();
+        kites.sort_unstable();
+        Some(Polykite { kites, images })
+    }
+
+    pub fn hat() -> Self {
+        Polykite::new(&HatPose::default().kites()).unwrap_or_default()
+    }
+
+    // Every copy that covers a kite
+    fn covering(&self, target: Kite) -> Vec<Vec<Kite>> {
+        let mut result = Vec::new();
+        for image in &self.images {
+            for kt in image.iter().filter(|kt| kt.k == target.k) {
+                let d = target.hex - kt.hex;
+                result.push(image.iter().map(|x| Kite { hex: x.hex + d, k: x.k }).collect());
+            }
+        }
+        result
+    }
+}
+
+// Whether the union of these kites has a hole
+fn has_hole(kites: &HashSet<Kite>) -> bool {
+    let edges: Vec<Edge> = kites.iter().flat_map(|kt| {
+        let v = kt.vertices();
+        (0..4).map(move |i| Edge { start: v[i], end: v[(i+1) % 4], length: EdgeLength::Short })
+    }).collect();
+    let all: HashSet<(Eisenstein, Eisenstein)> = edges.iter().map(|e| (e.start, e.end)).collect();
+    let boundary: Vec<Edge> = edges.into_iter().filter(|e| !all.contains(&(e.end, e.start))).collect();
+    outline::rings(&boundary).iter().any(|ring| {
+        let pts: Vec<_> = ring.iter().map(|e| e.start.to_point()).collect();
+        polygon::area(&pts) < 0.
+    })
+}
+
+// The result of a Heesch search: the number found and the coronas that
+// show it, each a list of copies of the shape given by their kites
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Heesch {
+    pub number: usize,
+    pub coronas: Vec<Vec<Vec<Kite>>>,
+}
+
+struct Search<'a> {
+    shape: &'a Polykite,
+    limit: usize,
+    covered: HashSet<Kite>,
+    layers: Vec<Vec<Vec<Kite>>>,
+    best: Heesch,
+}
+
+impl Search<'_> {
+    // Surround everything placed so far with one more corona, and go on
+    // from each way of doing so. True once the limit is reached.
+    fn corona(&mut self) -> bool {
+        if self.layers.len() - 1 > self.best.number {
+            self.best = Heesch { number: self.layers.len() - 1, coronas: self.layers[1..].to_vec() };
+        }
+        if self.best.number >= self.limit {
+            return true;
+        }
+        // Only the last layer can still have uncovered kites around it
+        let mut needed: Vec<Kite> = self.layers[self.layers.len() - 1].iter()
+            .flatten()
+            .flat_map(|kt| kt.vertices())
+            .flat_map(kites_at)
+            .filter(|kt| !self.covered.contains(kt))
+            .collect();
+        needed.sort_unstable();
+        needed.dedup();
+        self.layers.push(Vec::new());
+        let done = self.fill(&needed);
+        self.layers.pop();
+        done
+    }
+
+    // Cover every needed kite with copies of the shape, the kite with the
+    // fewest ways to cover it first
+    fn fill(&mut self, needed: &[Kite]) -> bool {
+        let options = needed.iter()
+            .filter(|kt| !self.covered.contains(kt))
+            .map(|kt| {
+                self.shape.covering(*kt).into_iter()
+                    .filter(|copy| copy.iter().all(|x| !self.covered.contains(x)))
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(|o| o.len());
+        let options = match options {
+            Some(o) => o,
+            None => {
+                // A complete corona, so long as it leaves no holes
+                return !has_hole(&self.covered) && self.corona();
+            },
+        };
+        for copy in options {
+            self.covered.extend(copy.iter().copied());
+            self.layers.last_mut().unwrap().push(copy.clone());
+            let done = self.fill(needed);
+            self.layers.last_mut().unwrap().pop();
+            for kt in &copy {
+                self.covered.remove(kt);
+            }
+            if done {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// The Heesch number of a polykite, searching no further than limit coronas.
+// A result of limit means at least limit, as for any shape that tiles the
+// plane. A shape with no kites has none.
+pub fn heesch(shape: &Polykite, limit: usize) -> Heesch {
+    if shape.kites.is_empty() {
+        return Heesch::default();
+    }
+    let mut s = Search {
+        shape,
+        limit,
+        covered: shape.kites.iter().copied().collect(),
+        layers: vec![vec![shape.kites.clone()]],
+        best: Heesch::default(),
+    };
+    s.corona();
+    s.best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metatile::{self, Metatile};
+
+    #[test]
+    fn empty_shape() {
+        assert_eq!(Polykite::new(&[]), None);
+        assert_eq!(heesch(&Polykite::default(), 3), Heesch::default());
+    }
+
+    #[test]
+    fn hat_reaches_the_limit() {
+        let h = heesch(&Polykite::hat(), 2);
+        assert_eq!(h.number, 2);
+        assert_eq!(h.coronas.len(), 2);
+        let copies: Vec<Vec<Kite>> = h.coronas.iter().flatten().cloned().collect();
+        assert!(copies.iter().all(|c| HatPose::from_kites(c).is_some()));
+    }
+
+    #[test]
+    fn non_tilers() {
+        // Five of the six kites of a hexagon: nothing fits the notch
+        let five: Vec<Kite> = (0..5).map(|k| kite(Hex::default(), k)).collect();
+        assert_eq!(heesch(&Polykite::new(&five).unwrap(), 3).number, 0);
+
+        // A hat missing one kite can be surrounded once, but no more
+        let mut kites = HatPose::default().kites().to_vec();
+        kites.remove(1);
+        let h = heesch(&Polykite::new(&kites).unwrap(), 3);
+        assert_eq!(h.number, 1);
+        assert_eq!(h.coronas.len(), 1);
+    }
+
+    #[test]
+    fn corona_in_a_supertile() {
+        let patch = Patch::new(metatile::supertile(Metatile::H, 3));
+        let c = patch.centroid().unwrap();
+        let t = patch.tile_at(c).unwrap();
+        let rings = coronas(&patch, &[t], 1);
+        assert_eq!(rings.len(), 1);
+        assert!(!rings[0].contains(&t));
+
+        let inner: HashSet<Kite> = patch.tiles[t].kites().iter().copied().collect();
+        let mut covered = inner.clone();
+        covered.extend(rings[0].iter().flat_map(|&i| patch.tiles[i].kites()));
+        assert!(surrounds(&inner, &covered));
+        // Every tile of the ring is needed
+        for &i in &rings[0] {
+            let fewer: HashSet<Kite> = covered.difference(&patch.tiles[i].kites().iter().copied().collect())
+                .copied()
+                .collect();
+            assert!(!surrounds(&inner, &fewer));
+        }
+    }
+}
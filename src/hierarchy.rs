@@ -0,0 +1,157 @@
+// The supertile tree of a substitution patch. Hats are numbered as in
+// `tiles`, supertiles by their index in `supertiles`, the root first.
+
+use std::fmt::Write;
+
+use crate::metatile::Metatile;
+use crate::tile::HatPose;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Supertile {
+    pub kind: Metatile,
+    // 1 for a metatile, one more for each substitution above that
+    pub level: u32,
+    pub parent: Option<usize>,
+    // Hats for a metatile, otherwise supertiles of the level below
+    pub children: Vec<usize>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Hierarchy {
+    pub tiles: Vec<HatPose>,
+    pub supertiles: Vec<Supertile>,
+    // The metatile each hat belongs to
+    pub tile_parents: Vec<usize>,
+}
+
+impl Hierarchy {
+    pub fn root(&self) -> Option<usize> {
+        if self.supertiles.is_empty() { None } else { Some(0) }
+    }
+
+    pub fn levels(&self) -> u32 {
+        self.root().map_or(0, |r| self.supertiles[r].level)
+    }
+
+    // The supertile of the given level that holds a hat
+    pub fn parent(&self, tile: usize, level: u32) -> Option<usize> {
+        if level == 0 {
+            return None;
+        }
+        let mut s = *self.tile_parents.get(tile)?;
+        while self.supertiles[s].level < level {
+            s = self.supertiles[s].parent?;
+        }
+        Some(s)
+    }
+
+    pub fn children(&self, supertile: usize) -> &[usize] {
+        &self.supertiles[supertile].children
+    }
+
+    // The other hats of the same metatile
+    pub fn siblings(&self, tile: usize) -> Vec<usize> {
+        match self.tile_parents.get(tile) {
+            Some(&p) => self.children(p).iter().copied().filter(|&t| t != tile).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Every hat below a supertile
+    pub fn tiles_of(&self, supertile: usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut stack = vec![supertile];
+        while let Some(s) = stack.pop() {
+            let st = &self.supertiles[s];
+            if st.level == 1 {
+                result.extend_from_slice(&st.children);
+            } else {
+                stack.extend(st.children.iter().rev());
+            }
+        }
+        result
+    }
+
+    // Where a hat sits in the tree, written as the kind of the root, then
+    // the position of each supertile among its parent's children and finally
+    // that of the hat in its metatile, e.g. "H.2.0.3"
+    pub fn address(&self, tile: usize) -> Option<String> {
+        let mut path = Vec::new();
+        let mut child = tile;
+        let mut s = *self.tile_parents.get(tile)?;
+        loop {
+            path.push(self.children(s).iter().position(|&c| c == child)?);
+            match self.supertiles[s].parent {
+                Some(p) => { child = s; s = p; },
+                None => break,
+            }
+        }
+        let mut a = self.supertiles[s].kind.name().to_string();
+        for i in path.iter().rev() {
+            let _ = write!(a, ".{}", i);
+        }
+        Some(a)
+    }
+
+    // The hat at an address, if it names a hat of this tree
+    pub fn tile_at_address(&self, address: &str) -> Option<usize> {
+        let (kind, path) = parse_address(address)?;
+        let root = self.root()?;
+        if self.supertiles[root].kind != kind || path.len() as u32 != self.levels() {
+            return None;
+        }
+        let mut t = root;
+        for &i in &path {
+            t = *self.children(t).get(i)?;
+        }
+        Some(t)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut s = String::from("{\n  \"tiles\": [");
+        for (i, t) in self.tiles.iter().enumerate() {
+            let parent = self.tile_parents.get(i).map_or("null".to_string(), |p| p.to_string());
+            let address = self.address(i).map_or("null".to_string(), |a| format!("\"{}\"", a));
+            let _ = write!(s, "{}\n    {{\"hex\": [{}, {}], \"rotation\": {}, \"reflected\": {}, \"parent\": {}, \"address\": {}}}",
+                           if i == 0 { "" } else { "," },
+                           t.hex.q, t.hex.r, t.rotation, t.reflected, parent, address);
+        }
+        s.push_str("\n  ],\n  \"supertiles\": [");
+        for (i, st) in self.supertiles.iter().enumerate() {
+            let parent = st.parent.map_or("null".to_string(), |p| p.to_string());
+            let children: Vec<String> = st.children.iter().map(|c| c.to_string()).collect();
+            let _ = write!(s, "{}\n    {{\"kind\": \"{}\", \"level\": {}, \"parent\": {}, \"children\": [{}]}}",
+                           if i == 0 { "" } else { "," },
+                           st.kind.name(), st.level, parent, children.join(", "));
+        }
+        s.push_str("\n  ]\n}\n");
+        s
+    }
+}
+
+// The kind of the root and the path below it, from an address such as "H.2.0.3"
+pub fn parse_address(address: &str) -> Option<(Metatile, Vec<usize>)> {
+    let mut parts = address.split('.');
+    let kind = Metatile::from_name(parts.next()?)?;
+    let path = parts.map(|p| p.parse().ok()).collect::<Option<Vec<usize>>>()?;
+    Some((kind, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metatile::{self, Metatile};
+
+    #[test]
+    fn addresses_round_trip() {
+        let h = metatile::hierarchy(Metatile::H, 3);
+        assert!(!h.tiles.is_empty());
+        for t in 0..h.tiles.len() {
+            let address = h.address(t).unwrap();
+            assert!(address.starts_with("H."));
+            assert_eq!(h.tile_at_address(&address), Some(t), "{}", address);
+        }
+        assert_eq!(h.tile_at_address("T.0.0.0"), None);
+        assert_eq!(h.tile_at_address("H.0.0"), None);
+        assert_eq!(h.tile_at_address("H.0.0.99"), None);
+    }
+}
@@ -0,0 +1,438 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Add, Sub, Neg, Mul};
+
+// A lattice whose points can be the vertices of a tile. xy gives exact
+// coordinates in some affine image of the plane, enough for orientation and
+// point-in-polygon tests.
+pub trait Vertex: Copy + Eq + Hash + Ord + Debug + Add<Output = Self> + Sub<Output = Self> {
+    fn to_point(self) -> (f64, f64);
+    fn xy(self) -> (Root3, Root3);
+
+    // Direction of this vector in whole degrees
+    fn angle(self) -> i32 {
+        let p = self.to_point();
+        (p.1.atan2(p.0).to_degrees().round() as i32).rem_euclid(360)
+    }
+}
+
+// m + n*sqrt(3)
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Default)]
+pub struct Root3 {
+    pub m: i64,
+    pub n: i64,
+}
+
+impl Root3 {
+    pub const fn new(m: i64, n: i64) -> Root3 {
+        Root3 { m, n }
+    }
+
+    pub fn signum(self) -> i64 {
+        let (m, n) = (self.m.signum(), self.n.signum());
+        if m == n || n == 0 {
+            m
+        } else if m == 0 {
+            n
+        } else {
+            // Opposite signs: compare m^2 with 3 n^2
+            m * (self.m*self.m - 3*self.n*self.n).signum()
+        }
+    }
+}
+
+impl From<i64> for Root3 {
+    fn from(m: i64) -> Root3 { Root3::new(m, 0) }
+}
+
+impl Add for Root3 {
+    type Output = Root3;
+    fn add(self, o: Root3) -> Root3 { Root3::new(self.m + o.m, self.n + o.n) }
+}
+
+impl Sub for Root3 {
+    type Output = Root3;
+    fn sub(self, o: Root3) -> Root3 { Root3::new(self.m - o.m, self.n - o.n) }
+}
+
+impl Mul for Root3 {
+    type Output = Root3;
+    fn mul(self, o: Root3) -> Root3 {
+        Root3::new(self.m*o.m + 3*self.n*o.n, self.m*o.n + self.n*o.m)
+    }
+}
+
+impl PartialOrd for Root3 {
+    fn partial_cmp(&self, o: &Root3) -> Option<Ordering> { Some(self.cmp(o)) }
+}
+
+impl Ord for Root3 {
+    fn cmp(&self, o: &Root3) -> Ordering { (*self - *o).signum().cmp(&0) }
+}
+
+// Eisenstein integer a + b*w, w = exp(2*pi*i/3).
+//
+// Every vertex of the hexagonal kite grid is an Eisenstein integer when one
+// lattice unit is a short hat edge. Hex centres sit on the sublattice
+// spanned by 4+2w and 2+4w, a hexagon has side 2 and its kites have a long
+// edge (the apothem) of length sqrt(3).
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct Eisenstein {
+    pub a: i64,
+    pub b: i64,
+}
+
+pub const fn eis(a: i64, b: i64) -> Eisenstein {
+    Eisenstein { a, b }
+}
+
+// Real length of one lattice unit, i.e. of a short hat edge
+pub const UNIT: f64 = 0.5;
+
+impl Eisenstein {
+    pub const ZERO: Eisenstein = eis(0, 0);
+
+    // Counter-clockwise rotation by k * 60 degrees
+    pub fn rotate(self, k: i32) -> Eisenstein {
+        let mut z = self;
+        for _ in 0..k.rem_euclid(6) {
+            z = eis(z.a - z.b, z.a);
+        }
+        z
+    }
+
+    // Mirror image in the y axis (x -> -x)
+    pub fn mirror(self) -> Eisenstein {
+        eis(self.b - self.a, self.b)
+    }
+
+    // Squared length, in lattice units
+    pub fn norm(self) -> i64 {
+        self.a*self.a - self.a*self.b + self.b*self.b
+    }
+
+    pub fn to_point(self) -> (f64, f64) {
+        let s3 = 3_f64.sqrt();
+        ( (self.a as f64 - self.b as f64 / 2.) * UNIT,
+          (self.b as f64 * s3 / 2.) * UNIT )
+    }
+
+    // Nearest lattice point to a point in real coordinates
+    pub fn from_point(x: f64, y: f64) -> Eisenstein {
+        let s3 = 3_f64.sqrt();
+        let fb = (y / UNIT) * 2. / s3;
+        let fa = x / UNIT + fb / 2.;
+        let (a0, b0) = (fa.floor() as i64, fb.floor() as i64);
+
+        let mut best = eis(a0, b0);
+        let mut best_d = f64::MAX;
+        for (da, db) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let z = eis(a0 + da, b0 + db);
+            let p = z.to_point();
+            let d = (p.0 - x).powi(2) + (p.1 - y).powi(2);
+            if d < best_d {
+                best = z;
+                best_d = d;
+            }
+        }
+        best
+    }
+
+}
+
+impl Vertex for Eisenstein {
+    fn to_point(self) -> (f64, f64) {
+        Eisenstein::to_point(self)
+    }
+
+    // Shears the lattice onto the integer grid
+    fn xy(self) -> (Root3, Root3) {
+        (Root3::from(2*self.a - self.b), Root3::from(self.b))
+    }
+}
+
+impl Add for Eisenstein {
+    type Output = Eisenstein;
+    fn add(self, o: Eisenstein) -> Eisenstein { eis(self.a + o.a, self.b + o.b) }
+}
+
+impl Sub for Eisenstein {
+    type Output = Eisenstein;
+    fn sub(self, o: Eisenstein) -> Eisenstein { eis(self.a - o.a, self.b - o.b) }
+}
+
+impl Neg for Eisenstein {
+    type Output = Eisenstein;
+    fn neg(self) -> Eisenstein { eis(-self.a, -self.b) }
+}
+
+impl Mul for Eisenstein {
+    type Output = Eisenstein;
+    // (a + bw)(c + dw) with w^2 = -1 - w
+    fn mul(self, o: Eisenstein) -> Eisenstein {
+        eis(self.a*o.a - self.b*o.b, self.a*o.b + self.b*o.a - self.b*o.b)
+    }
+}
+
+// a + b*z + c*z^2 + d*z^3 with z = exp(i*pi/6), so z^4 = z^2 - 1.
+//
+// Points reached by unit steps in multiples of 30 degrees, such as the
+// vertices of a spectre tiling. Every Eisenstein integer is one of these.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct Cyclotomic {
+    pub a: i64,
+    pub b: i64,
+    pub c: i64,
+    pub d: i64,
+}
+
+pub const fn cyc(a: i64, b: i64, c: i64, d: i64) -> Cyclotomic {
+    Cyclotomic { a, b, c, d }
+}
+
+impl Cyclotomic {
+    pub const ZERO: Cyclotomic = cyc(0, 0, 0, 0);
+    pub const ONE: Cyclotomic = cyc(1, 0, 0, 0);
+
+    // Counter-clockwise rotation by k * 30 degrees
+    pub fn rotate(self, k: i32) -> Cyclotomic {
+        let mut z = self;
+        for _ in 0..k.rem_euclid(12) {
+            z = cyc(-z.d, z.a, z.b + z.d, z.c);
+        }
+        z
+    }
+
+    // Mirror image in the y axis (x -> -x), matching Eisenstein::mirror
+    pub fn mirror(self) -> Cyclotomic {
+        cyc(-self.a - self.c, -self.b, self.c, self.b + self.d)
+    }
+
+    pub fn to_point(self) -> (f64, f64) {
+        let (x, y) = self.xy();
+        let real = |r: Root3| (r.m as f64 + r.n as f64 * 3_f64.sqrt()) / 2. * UNIT;
+        (real(x), real(y))
+    }
+
+    pub fn to_eisenstein(self) -> Option<Eisenstein> {
+        if self.b == 0 && self.d == 0 {
+            Some(eis(self.a + self.c, self.c))
+        } else {
+            None
+        }
+    }
+}
+
+impl Vertex for Cyclotomic {
+    fn to_point(self) -> (f64, f64) {
+        Cyclotomic::to_point(self)
+    }
+
+    // Twice the real coordinates, in lattice units
+    fn xy(self) -> (Root3, Root3) {
+        (Root3::new(2*self.a + self.c, self.b), Root3::new(self.b + 2*self.d, self.c))
+    }
+}
+
+impl From<Eisenstein> for Cyclotomic {
+    // w = z^4 = z^2 - 1
+    fn from(z: Eisenstein) -> Cyclotomic {
+        cyc(z.a - z.b, 0, z.b, 0)
+    }
+}
+
+impl Add for Cyclotomic {
+    type Output = Cyclotomic;
+    fn add(self, o: Cyclotomic) -> Cyclotomic {
+        cyc(self.a + o.a, self.b + o.b, self.c + o.c, self.d + o.d)
+    }
+}
+
+impl Sub for Cyclotomic {
+    type Output = Cyclotomic;
+    fn sub(self, o: Cyclotomic) -> Cyclotomic {
+        cyc(self.a - o.a, self.b - o.b, self.c - o.c, self.d - o.d)
+    }
+}
+
+impl Neg for Cyclotomic {
+    type Output = Cyclotomic;
+    fn neg(self) -> Cyclotomic { cyc(-self.a, -self.b, -self.c, -self.d) }
+}
+
+// Axial coordinates of a hexagon in the kite grid (flat-topped hexagons)
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct Hex {
+    pub q: i64,
+    pub r: i64,
+}
+
+pub const fn hex(q: i64, r: i64) -> Hex {
+    Hex { q, r }
+}
+
+impl Hex {
+    // Counter-clockwise rotation by k * 60 degrees about the origin hexagon
+    pub fn rotate(self, k: i32) -> Hex {
+        let mut h = self;
+        for _ in 0..k.rem_euclid(6) {
+            h = hex(-h.r, h.q + h.r);
+        }
+        h
+    }
+
+    // Mirror image in the y axis, matching Eisenstein::mirror
+    pub fn mirror(self) -> Hex {
+        hex(-self.q, self.q + self.r)
+    }
+
+    // Number of steps between two hexagons
+    pub fn distance(self, other: Hex) -> i64 {
+        let d = self - other;
+        (d.q.abs() + d.r.abs() + (d.q + d.r).abs()) / 2
+    }
+
+    pub fn center(self) -> Eisenstein {
+        eis(4*self.q + 2*self.r, 2*self.q + 4*self.r)
+    }
+
+    // The hexagon centred on z, if z is a hex centre
+    pub fn from_center(z: Eisenstein) -> Option<Hex> {
+        // Invert a = 4q + 2r, b = 2q + 4r
+        let q6 = 2*z.a - z.b;
+        let r6 = 2*z.b - z.a;
+        if q6 % 6 == 0 && r6 % 6 == 0 {
+            Some(hex(q6 / 6, r6 / 6))
+        } else {
+            None
+        }
+    }
+
+    // The hexagon containing a point in real coordinates
+    pub fn from_point(x: f64, y: f64) -> Hex {
+        let s3 = 3_f64.sqrt();
+        let size = 2. * UNIT;
+        let fq = (2./3. * x) / size;
+        let fr = (-1./3. * x + s3/3. * y) / size;
+        let fs = -fq - fr;
+
+        let (mut q, mut r, s) = (fq.round(), fr.round(), fs.round());
+        let (dq, dr, ds) = ((q - fq).abs(), (r - fr).abs(), (s - fs).abs());
+        if dq > dr && dq > ds {
+            q = -r - s;
+        } else if dr > ds {
+            r = -q - s;
+        }
+        hex(q as i64, r as i64)
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+    fn add(self, o: Hex) -> Hex { hex(self.q + o.q, self.r + o.r) }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+    fn sub(self, o: Hex) -> Hex { hex(self.q - o.q, self.r - o.r) }
+}
+
+impl Neg for Hex {
+    type Output = Hex;
+    fn neg(self) -> Hex { hex(-self.q, -self.r) }
+}
+
+// One of the six kites of a hexagon: kite k holds the hexagon vertex in
+// direction k * 60 degrees from the centre
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct Kite {
+    pub hex: Hex,
+    pub k: i32,
+}
+
+pub fn kite(hex: Hex, k: i32) -> Kite {
+    Kite { hex, k: k.rem_euclid(6) }
+}
+
+impl Kite {
+    // Outline, counter-clockwise from the hexagon centre
+    pub fn vertices(self) -> [Eisenstein; 4] {
+        let c = self.hex.center();
+        let apothem = eis(2, 1);
+        [c,
+         c + apothem.rotate(self.k - 1),
+         c + eis(2, 0).rotate(self.k),
+         c + apothem.rotate(self.k)]
+    }
+
+    pub fn centroid(self) -> (f64, f64) {
+        let pts = self.vertices();
+        let c = pts.iter().fold((0., 0.), |acc, z| {
+            let p = z.to_point();
+            (acc.0 + p.0, acc.1 + p.1)
+        });
+        (c.0 / 4., c.1 / 4.)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root3_sign_and_order() {
+        assert_eq!(Root3::new(0, 0).signum(), 0);
+        assert_eq!(Root3::new(2, -1).signum(), 1);
+        assert_eq!(Root3::new(1, -1).signum(), -1);
+        assert_eq!(Root3::new(-2, 1).signum(), -1);
+        assert_eq!(Root3::new(-1, 1).signum(), 1);
+        let mut v = vec![Root3::new(2, 0), Root3::new(0, 1), Root3::new(-1, 1), Root3::new(1, 1)];
+        v.sort();
+        assert_eq!(v, [Root3::new(-1, 1), Root3::new(0, 1), Root3::new(2, 0), Root3::new(1, 1)]);
+    }
+
+    #[test]
+    fn eisenstein_rotate_and_mirror() {
+        let z = eis(3, -2);
+        assert_eq!(eis(1, 0).rotate(1), eis(1, 1));
+        assert_eq!(eis(1, 0).rotate(2), eis(0, 1));
+        assert_eq!(z.rotate(6), z);
+        assert_eq!(z.rotate(-1).rotate(1), z);
+        assert_eq!(z.mirror().mirror(), z);
+        assert_eq!(eis(1, 0).mirror(), eis(-1, 0));
+        assert_eq!(z.rotate(2).norm(), z.norm());
+        // Mirroring turns rotations the other way
+        assert_eq!(z.rotate(1).mirror(), z.mirror().rotate(-1));
+        let (x, y) = z.to_point();
+        assert_eq!(Eisenstein::from_point(x + 0.1, y - 0.1), z);
+    }
+
+    #[test]
+    fn cyclotomic_matches_eisenstein() {
+        for z in [eis(1, 0), eis(0, 1), eis(3, -2), eis(-4, 5)] {
+            let c = Cyclotomic::from(z);
+            assert_eq!(c.to_eisenstein(), Some(z));
+            assert_eq!(c.rotate(2).to_eisenstein(), Some(z.rotate(1)));
+            assert_eq!(c.mirror().to_eisenstein(), Some(z.mirror()));
+            let (p, q) = (c.to_point(), z.to_point());
+            assert!((p.0 - q.0).abs() < 1e-9 && (p.1 - q.1).abs() < 1e-9);
+        }
+        assert_eq!(Cyclotomic::ONE.rotate(1).to_eisenstein(), None);
+        assert_eq!(Cyclotomic::ONE.rotate(12), Cyclotomic::ONE);
+    }
+
+    #[test]
+    fn hex_centres() {
+        for h in [hex(0, 0), hex(2, -1), hex(-3, 4)] {
+            assert_eq!(Hex::from_center(h.center()), Some(h));
+            assert_eq!(h.rotate(1).center(), h.center().rotate(1));
+            assert_eq!(h.mirror().center(), h.center().mirror());
+            let (x, y) = h.center().to_point();
+            assert_eq!(Hex::from_point(x, y), h);
+        }
+        assert_eq!(Hex::from_center(eis(1, 0)), None);
+        assert_eq!(hex(0, 0).distance(hex(2, -1)), 2);
+    }
+}
@@ -0,0 +1,210 @@
+// Whether a hand-built patch can occur in a hat tiling, decided by finding it
+// in a supertile
+
+use std::collections::HashMap;
+
+use crate::metatile::{self, Metatile, MAX_LEVEL};
+use crate::patch::Patch;
+use crate::tile::{HatPose, Isometry};
+
+// Where a patch occurs: the isometry takes tile i of the patch onto tile
+// tiles[i] of metatile::supertile(kind, level)
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Witness {
+    pub kind: Metatile,
+    pub level: u32,
+    pub isometry: Isometry,
+    pub tiles: Vec<usize>,
+}
+
+// Hats in the H supertile of each level, and the shorter side of its
+// bounds rounded down, so that a level can be chosen without building any
+const H_SIZES: [(usize, f64); MAX_LEVEL as usize + 1] = [
+    (1, 2.1), (4, 4.7), (25, 12.), (169, 32.9),
+    (1156, 88.3), (7921, 233.8), (54289, 614.8), (372100, 1612.5),
+];
+
+// The smallest supertile many times wider than the patch. Rarer clusters
+// of even a few hats are missing from level 3, while level 4 holds every
+// cluster of up to six hats that level 5 does.
+pub(crate) fn level_for(patch: &Patch) -> u32 {
+    let size = patch.bounds().map_or(0., |b| b.width().max(b.height()));
+    (4..MAX_LEVEL).find(|&l| H_SIZES[l as usize].1 >= 8. * size).unwrap_or(MAX_LEVEL)
+}
+
+// The smallest supertile with room for a patch of n tiles many times over
+pub(crate) fn level_for_tiles(n: usize) -> u32 {
+    (2..MAX_LEVEL).find(|&l| H_SIZES[l as usize].0 >= 10 * n).unwrap_or(MAX_LEVEL)
+}
+
+// The ways a patch built up one tile at a time can still be placed in an H
+// supertile
+#[derive(Debug, Clone)]
+pub struct Embedding {
+    pub level: u32,
+    tiles: Vec<HatPose>,
+    index: HashMap<HatPose, usize>,
+    // How many times each pose has been added. A repeated tile has no
+    // placement, as an isometry cannot take two copies onto different tiles.
+    added: HashMap<HatPose, usize>,
+    // Isometries taking the patch into the supertile, after each tile added
+    candidates: Vec<Vec<Isometry>>,
+    // The patch's tiles, in the order added
+    poses: Vec<HatPose>,
+}
+
+impl Embedding {
+    pub fn new(level: u32) -> Self {
+        let tiles = metatile::supertile(Metatile::H, level);
+        let index = tiles.iter().enumerate().map(|(i, t)| (*t, i)).collect();
+        Embedding { level, tiles, index, added: HashMap::new(), candidates: Vec::new(), poses: Vec::new() }
+    }
+
+    // Whether some placement of the patch so far puts pose on a tile
+    pub fn fits(&self, pose: &HatPose) -> bool {
+        if self.added.contains_key(pose) {
+            return false;
+        }
+        match self.candidates.last() {
+            Some(c) => c.iter().any(|iso| self.index.contains_key(&iso.apply_to_pose(pose))),
+            None => !self.tiles.is_empty(),
+        }
+    }
+
+    pub fn push(&mut self, pose: &HatPose) {
+        let places = match self.candidates.last() {
+            _ if self.added.contains_key(pose) => Vec::new(),
+            Some(c) => c.iter().copied()
+                .filter(|iso| self.index.contains_key(&iso.apply_to_pose(pose)))
+                .collect(),
+            None => self.tiles.iter().map(|t| t.isometry().compose(&pose.isometry().inverse())).collect(),
+        };
+        self.candidates.push(places);
+        *self.added.entry(*pose).or_default() += 1;
+        self.poses.push(*pose);
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(pose) = self.poses.pop() {
+            if let Some(n) = self.added.get_mut(&pose) {
+                *n -= 1;
+                if *n == 0 {
+                    self.added.remove(&pose);
+                }
+            }
+        }
+        self.candidates.pop();
+    }
+
+    // Add tiles in turn, stopping with the index of the first that no
+    // placement of those before it takes
+    pub fn extend(&mut self, tiles: &[HatPose]) -> Result<(), usize> {
+        for (i, t) in tiles.iter().enumerate() {
+            if !self.fits(t) {
+                return Err(i);
+            }
+            self.push(t);
+        }
+        Ok(())
+    }
+
+    // Every placement of the patch so far, in the order of the supertile's
+    // tiles
+    pub fn placements(&self) -> &[Isometry] {
+        self.candidates.last().map_or(&[], |c| c.as_slice())
+    }
+
+    // The first placement of the patch, in the order of the supertile's tiles
+    pub fn placement(&self) -> Option<Isometry> {
+        self.candidates.last()?.first().copied()
+    }
+
+    // Index of the supertile tile at a pose
+    pub fn tile_at(&self, pose: &HatPose) -> Option<usize> {
+        self.index.get(pose).copied()
+    }
+}
+
+// The ways a patch can be placed in an H supertile chosen by its size, or
+// the index of the first tile, in patch order, that no placement of the
+// tiles before it can be extended to. Every legal patch occurs in a large
+// enough supertile, but the one searched is only chosen by the size of the
+// patch, so a rare legal patch may still be rejected.
+pub fn embed(patch: &Patch) -> Result<Embedding, usize> {
+    let mut e = Embedding::new(level_for(patch));
+    e.extend(&patch.tiles)?;
+    Ok(e)
+}
+
+// A witness that the patch occurs in an H supertile, or as for embed the
+// first tile that cannot be placed
+pub fn check(patch: &Patch) -> Result<Witness, usize> {
+    let e = embed(patch)?;
+    let isometry = e.placement().unwrap_or_else(Isometry::identity);
+    let tiles = patch.tiles.iter().filter_map(|t| e.tile_at(&isometry.apply_to_pose(t))).collect();
+    Ok(Witness { kind: Metatile::H, level: e.level, isometry, tiles })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_match_supertiles() {
+        for level in 0..=5 {
+            let patch = Patch::new(metatile::supertile(Metatile::H, level));
+            let b = patch.bounds().unwrap();
+            let (n, side) = H_SIZES[level as usize];
+            assert_eq!(patch.len(), n);
+            assert!((0. ..0.1).contains(&(b.width().min(b.height()) - side)), "level {}", level);
+        }
+    }
+
+    #[test]
+    fn grown_patches_pass() {
+        for seed in 1..=3 {
+            let patch = crate::grow::grow(&Patch::default(), 40, seed).unwrap();
+            let w = check(&patch).unwrap();
+            let supertile = metatile::supertile(w.kind, w.level);
+            assert_eq!(w.tiles.len(), patch.len());
+            for (t, &i) in patch.tiles.iter().zip(&w.tiles) {
+                assert_eq!(w.isometry.apply_to_pose(t), supertile[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_cluster_fails_at_its_first_bad_tile() {
+        use crate::lattice::hex;
+        let a = HatPose::default();
+        let b = crate::tile::attach(&a, 1, false).unwrap()[0];
+        let c = HatPose::new(hex(-1, 0), 1, false);
+        let patch = Patch::new(vec![a, b, c]);
+        assert!(patch.overlapping_pairs().is_empty());
+        assert!(check(&Patch::new(vec![a, b])).is_ok());
+        assert_eq!(check(&patch), Err(2));
+    }
+
+    #[test]
+    fn repeated_tiles_fail() {
+        let hat = HatPose::default();
+        assert_eq!(check(&Patch::new(vec![hat, hat])), Err(1));
+
+        let mut tiles = metatile::supertile(Metatile::H, 2);
+        tiles.push(tiles[3]);
+        let patch = Patch::new(tiles);
+        assert_eq!(patch.overlapping_pairs(), vec![(3, 25)]);
+        assert_eq!(check(&patch), Err(25));
+
+        // Popping the repeat leaves the patch placeable again
+        let mut e = Embedding::new(4);
+        assert_eq!(e.extend(&patch.tiles[..25]), Ok(()));
+        assert!(!e.fits(&patch.tiles[25]));
+        e.push(&patch.tiles[25]);
+        assert!(e.placements().is_empty());
+        e.pop();
+        assert!(!e.placements().is_empty());
+        e.pop();
+        assert!(e.fits(&patch.tiles[24]));
+    }
+}
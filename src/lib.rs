@@ -0,0 +1,18 @@
+pub mod atlas;
+pub mod cluster;
+pub mod deflation;
+pub mod family;
+pub mod grow;
+pub mod heesch;
+pub mod hierarchy;
+pub mod lattice;
+pub mod legality;
+pub mod metatile;
+pub mod outline;
+pub mod patch;
+pub mod polygon;
+pub mod random;
+pub mod region;
+pub mod spectre;
+pub mod tile;
+pub mod validate;
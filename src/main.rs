@@ -1,111 +1,88 @@
-use nannou::prelude::*;
-
-#[macro_use]
+use std::collections::HashSet;
 
-#[path = "tile.rs"]
-mod tile;
+use nannou::prelude::*;
 
-use tile::*;
+use aperiodic_monotile::cluster::{self, Cluster};
+use aperiodic_monotile::deflation;
+use aperiodic_monotile::family::{self, TileShape};
+use aperiodic_monotile::grow;
+use aperiodic_monotile::heesch;
+use aperiodic_monotile::hierarchy::Hierarchy;
+use aperiodic_monotile::lattice::{Cyclotomic, Hex};
+use aperiodic_monotile::legality;
+use aperiodic_monotile::metatile::{self, Metatile};
+use aperiodic_monotile::outline;
+use aperiodic_monotile::patch::Patch;
+use aperiodic_monotile::spectre::{self, SpectreKind, SpectrePose};
+use aperiodic_monotile::tile::{self, *};
+use aperiodic_monotile::validate;
 
 struct DrawProps {
     fill_color1: nannou::color::Srgb<u8>,
     fill_color2: nannou::color::Srgb<u8>,
     edge_color: nannou::color::Srgb<u8>,
     edge_weight: f32,
+    curved: bool,
 }
 
+// Edges are given on the finer spectre lattice so that hats and spectres
+// share the same bookkeeping
 trait Drawable {
-    fn draw(&self, draw: &nannou::draw::Draw, xoff: f32, yoff: f32, scale: f32, props: &DrawProps);
-    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, dx: f64, dy: f64);
-    fn get_drawable_edges(&self) -> Vec<Edge>;
-}
-
-fn interpolate(p1: (f32,f32), p2: (f32,f32), t: f64) -> (f32,f32) {
-    return (
-        (p1.0 as f64 + t * ( p2.0 as f64 - p1.0 as f64)) as f32,
-        (p1.1 as f64 + t * ( p2.1 as f64 - p1.1 as f64)) as f32
-    )
-}
-
-impl Drawable for Unreflected {
-    fn draw(&self, draw: &nannou::draw::Draw, xoff: f32, yoff: f32, scale: f32, props: &DrawProps) {
-        let pts = self.polygon(xoff, yoff, scale);
-        let points = (0..13).map(|i| {
-            pt2(pts[i].0, pts[i].1)
-        });
-        draw.polygon()
-            .color(props.fill_color1)
-            .stroke(props.edge_color)
-            .stroke_weight(props.edge_weight)
-            .join_miter()
-            .points(points);
+    fn draw_outline(&self, draw: &nannou::draw::Draw, outline: &[(f64, f64)], xoff: f32, yoff: f32, scale: f32, props: &DrawProps);
+    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, offset: Hex);
+    fn get_drawable_edges(&self) -> Vec<Edge<Cyclotomic>>;
+    fn as_hat(&self) -> Option<HatPose>;
+}
 
-    }
+fn draw_polygon(draw: &nannou::draw::Draw, outline: &[(f64, f64)], reflected: bool, xoff: f32, yoff: f32, scale: f32, props: &DrawProps) {
+    let outline = if props.curved { spectre::curved(outline, reflected, 8) } else { outline.to_vec() };
+    let points = outline.iter().map(|p| pt2(p.0 as f32 * scale + xoff, p.1 as f32 * scale + yoff));
+    draw.polygon()
+        .color(if reflected { props.fill_color2 } else { props.fill_color1 })
+        .stroke(props.edge_color)
+        .stroke_weight(props.edge_weight)
+        .join_miter()
+        .points(points);
+}
 
-    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, dx: f64, dy: f64) {
-        dst.push(Box::new(tile::Unreflected {
-            cx: self.cx + dx,
-            cy: self.cy + dy,
-            angle: self.angle
-        }));
+impl Drawable for HatPose {
+    fn draw_outline(&self, draw: &nannou::draw::Draw, outline: &[(f64, f64)], xoff: f32, yoff: f32, scale: f32, props: &DrawProps) {
+        draw_polygon(draw, outline, self.reflected, xoff, yoff, scale, props);
     }
 
-    fn get_drawable_edges(&self) -> Vec<Edge> {
-        self.get_edges()
+    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, offset: Hex) {
+        dst.push(Box::new(self.translate(offset)));
     }
-}
 
-impl Drawable for Reflected {
-    fn draw(&self, draw: &nannou::draw::Draw, xoff: f32, yoff: f32, scale: f32, props: &DrawProps) {
-        let pts = self.polygon(xoff, yoff, scale);
-        let points = (0..13).map(|i| {
-            pt2(pts[i].0, pts[i].1)
-        });
-        draw.polygon()
-            .color(props.fill_color2)
-            .stroke(props.edge_color)
-            .stroke_weight(props.edge_weight)
-            .join_miter()
-            .points(points);
+    fn get_drawable_edges(&self) -> Vec<Edge<Cyclotomic>> {
+        self.get_edges().iter().map(|e| e.map(Cyclotomic::from)).collect()
+    }
 
+    fn as_hat(&self) -> Option<HatPose> {
+        Some(*self)
+    }
+}
 
+impl Drawable for SpectrePose {
+    fn draw_outline(&self, draw: &nannou::draw::Draw, outline: &[(f64, f64)], xoff: f32, yoff: f32, scale: f32, props: &DrawProps) {
+        draw_polygon(draw, outline, self.reflected, xoff, yoff, scale, props);
     }
 
-    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, dx: f64, dy: f64) {
-        dst.push(Box::new(tile::Reflected {
-            cx: self.cx + dx,
-            cy: self.cy + dy,
-            angle: self.angle
-        }));
+    fn append_to_vector(&self, dst: &mut Vec<Box<dyn Drawable>>, offset: Hex) {
+        dst.push(Box::new(self.translate(Cyclotomic::from(offset.center()))));
     }
 
-    fn get_drawable_edges(&self) -> Vec<Edge> {
+    fn get_drawable_edges(&self) -> Vec<Edge<Cyclotomic>> {
         self.get_edges()
     }
-}
 
-fn build_tile(tile: &tile::Tile, x: f64, y: f64, angle: i32) -> Result<Box<dyn Drawable>, i32> {
-    match tile {
-        tile::Tile::UNREFLECTED => Ok(Box::new(Unreflected::new(x, y, angle))),
-        tile::Tile::REFLECTED => Ok(Box::new(Reflected::new(x, y, angle))),
+    fn as_hat(&self) -> Option<HatPose> {
+        None
     }
 }
 
-fn interp_angles(start_angle: i32, end_angle: i32) -> Vec<f64> {
-    let mut result = Vec::new();
-
-    let angles = if start_angle > end_angle {
-        ( (start_angle as f64) * std::f64::consts::PI / 180.,
-           ((end_angle + 360) as f64) * std::f64::consts::PI / 180. )
-    } else {
-        ( (start_angle as f64) * std::f64::consts::PI / 180.,
-           (end_angle as f64) * std::f64::consts::PI / 180. )
-    };
-
-    for i in 0..25 {
-        result.push(angles.0 + (i as f64) / 24. * (angles.1 - angles.0));
-    }
-    return result
+fn build_tile(reflected: bool, x: f64, y: f64, rotation: i32) -> Box<dyn Drawable> {
+    Box::new(HatPose::new(Hex::from_point(x, y), rotation, reflected))
 }
 
 fn main() {
@@ -118,17 +95,43 @@ fn main() {
 
 struct Model {
     tiles: Vec<Box<dyn Drawable>>,
-    edges: Vec<tile::Edge>,
+    edges: Vec<Edge<Cyclotomic>>,
     current_point: Point2,
     show_edges: bool,
     scale: f64,
     debug: bool,
-    next_tile: tile::Tile,
-    angle: i32,
+    next_reflected: bool,
+    rotation: i32,
+    shape: TileShape,
+    // Supertile structure of the tiles, when they came from substitution
+    hierarchy: Option<Hierarchy>,
+    // Generate spectres rather than hats
+    spectres: bool,
+    curved: bool,
+    // Seed for the next random patch, and the one that made the tiles shown
+    seed: u64,
+    patch_seed: Option<u64>,
+    // Clusters to step through, worked out the first time they are asked for
+    clusters: Vec<Cluster>,
+    next_cluster: usize,
+}
+
+fn next_shape(shape: &TileShape) -> TileShape {
+    let shapes = [TileShape::hat(),
+                  TileShape::turtle(),
+                  TileShape::equilateral(),
+                  TileShape::chevron(),
+                  TileShape::comet()];
+    let i = shapes.iter().position(|s| s == shape).map_or(0, |i| i + 1);
+    shapes[i % shapes.len()]
 }
 
+const GROW_BY: usize = 50;
+const HEESCH_LIMIT: usize = 3;
+const CLUSTER_SIZE: usize = 4;
+
 fn snap_tolerance(scale: f64) -> f64 {
-    return 15. / scale
+    15. / scale
 }
 
 fn model(app: &App) -> Model {
@@ -163,144 +166,306 @@ fn model(app: &App) -> Model {
             show_edges: true,
             scale: 25.,
             debug: false,
-            next_tile: tile::Tile::UNREFLECTED,
-            angle: 0,
+            next_reflected: false,
+            rotation: 0,
+            shape: TileShape::hat(),
+            hierarchy: None,
+            spectres: false,
+            curved: false,
+            seed: seed_from_args(),
+            patch_seed: None,
+            clusters: Vec::new(),
+            next_cluster: 0,
     }
 }
 
-fn snap_to_edges(tile: &Box<dyn Drawable>, edges: &Vec<Edge>, tol: f64) -> (f64, f64) {
-    let mut result = (0., 0.);
+// The --seed given on the command line, otherwise 0
+fn seed_from_args() -> u64 {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|a| a == "--seed")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0)
+}
+
+// Offset that brings one of the tile's edges onto a nearby boundary edge
+fn snap_to_edges(tile: &dyn Drawable, edges: &[Edge<Cyclotomic>], tol: f64) -> Option<Hex> {
+    let mut result = None;
     let mut curr_l2 = tol;
 
     let e1 = tile.get_drawable_edges();
     for e in edges {
 
-        for i in 0..13 {
-            let dx = e.center.0 - e1[i].center.0;
-            let dy = e.center.1 - e1[i].center.1;
+        for te in &e1 {
+            if te.length != e.length {
+                continue;
+            }
+            if te.end - te.start != e.start - e.end {
+                continue;
+            }
+
+            let d = e.start - te.end;
+            let offset = match d.to_eisenstein().and_then(Hex::from_center) {
+                Some(h) => h,
+                None => continue,
+            };
+
+            let (dx, dy) = d.to_point();
             let l2 = dx*dx + dy*dy;
             if l2 < curr_l2 {
-                if (e1[i].angle + 180) % 360 != e.angle {
-                    continue;
-                }
-                if e1[i].length != e.length {
-                    continue;
-                }
-
-                result = (dx, dy);
+                result = Some(offset);
                 curr_l2 = l2;
             }
-
         }
     }
-    return result
+    result
 }
 
-fn snaps(edges: &Vec<tile::Edge>, tile: &Box<dyn Drawable>, tol: f64) -> bool {
+fn update_edges(model: &mut Model) {
+    let all: HashSet<Edge<Cyclotomic>> = model.tiles.iter().flat_map(|t| t.get_drawable_edges()).collect();
+    model.edges = model.tiles.iter()
+        .flat_map(|t| t.get_drawable_edges())
+        .filter(|e| !all.contains(&e.reversed()))
+        .collect();
+}
 
-    let de = tile.get_drawable_edges();
+fn overlaps_any(tile: &dyn Drawable, tiles: &[Box<dyn Drawable>]) -> bool {
+    let e1 = tile.get_drawable_edges();
+    tiles.iter().any(|t| tile::interiors_intersect(&e1, &t.get_drawable_edges()))
+}
 
-    for edge in edges {
-        for i in 0..13 {
-            if (edge.angle + 180)%360 != de[i].angle {
-                continue;
-            }
-            if edge.length != de[i].length {
-                continue;
-            }
+// Where the tile would land if dropped: snapped onto a nearby boundary edge if possible
+fn placed_tile(model: &Model, tile: &dyn Drawable) -> (Box<dyn Drawable>, bool) {
+    let offset = snap_to_edges(tile, &model.edges, snap_tolerance(model.scale));
+    let mut placed = Vec::new();
+    tile.append_to_vector(&mut placed, offset.unwrap_or_default());
+    (placed.pop().unwrap(), offset.is_some())
+}
 
-            let dx = edge.center.0 - de[i].center.0;
-            let dy = edge.center.1 - de[i].center.1;
-            let l2 = dx*dx + dy*dy;
-            if l2 < tol {
-                return true
-            }
-        }
+fn add_tile(model: &mut Model, tile: Box<dyn Drawable>) {
+
+    let (placed, _) = placed_tile(model, tile.as_ref());
+    if overlaps_any(placed.as_ref(), &model.tiles) {
+        println!("Tile overlaps existing tiles, not placed");
+        return;
     }
-    return false
+    model.tiles.push(placed);
+    model.hierarchy = None;
+    model.patch_seed = None;
+    update_edges(model);
 }
 
-// @todo Handle SHORT matching half a DOUBLE
-fn match_edges(t1: &Box<dyn Drawable>, t2: &Box<dyn Drawable>, skip: [bool; 13]) -> [bool; 13] {
-    let mut result = skip;
-    let e1 = t1.get_drawable_edges();
-    let e2 = t2.get_drawable_edges();
+// Replace the tiles with a level n H supertile, or Delta supertile of spectres
+fn load_supertile(model: &mut Model, level: u32) {
+    if model.spectres {
+        model.tiles = spectre::supertile(SpectreKind::Delta, level).into_iter()
+            .map(|t| Box::new(t) as Box<dyn Drawable>)
+            .collect();
+        model.hierarchy = None;
+        model.patch_seed = None;
+        update_edges(model);
+        println!("Level {} spectre supertile: {} tiles", level, model.tiles.len());
+        return;
+    }
+    let hierarchy = metatile::hierarchy(Metatile::H, level);
+    model.tiles = hierarchy.tiles.iter()
+        .map(|t| Box::new(*t) as Box<dyn Drawable>)
+        .collect();
+    model.hierarchy = Some(hierarchy);
+    update_edges(model);
+    println!("Level {} supertile: {} tiles", level, model.tiles.len());
+}
 
-    for i in 0..13 {
-        if result[i] { continue }
+// The model's tiles as a patch, if they are all hats
+fn hat_patch(model: &Model) -> Option<Patch> {
+    let hats: Option<Vec<HatPose>> = model.tiles.iter().map(|t| t.as_hat()).collect();
+    if hats.is_none() {
+        println!("Only patches of hats are supported");
+    }
+    hats.map(Patch::new)
+}
 
-        for j in 0..13 {
-            let dx = e2[j].center.0 - e1[i].center.0;
-            let dy = e2[j].center.1 - e1[i].center.1;
-            let l2 = dx*dx + dy*dy;
-            if l2 > 0.1 {
-                continue;
-            }
+fn set_hats(model: &mut Model, patch: Patch) {
+    model.tiles = patch.tiles.into_iter().map(|t| Box::new(t) as Box<dyn Drawable>).collect();
+    model.hierarchy = None;
+    model.patch_seed = patch.seed;
+    update_edges(model);
+}
 
-            let a1 = (e1[i].angle + 180) % 360;
-            let a2 = e2[j].angle;
-            if a1 != a2 {
-                continue;
-            }
-            result[i] = true;
-        }
+// Grow the hats by another GROW_BY random tiles
+fn grow_tiles(model: &mut Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    let seed = model.seed;
+    model.seed = model.seed.wrapping_add(1);
+    let target = patch.len().max(1) + GROW_BY;
+    match grow::grow(&patch, target, seed) {
+        Some(patch) => {
+            set_hats(model, patch);
+            println!("Grew to {} tiles with seed {}", model.tiles.len(), seed);
+        },
+        None => println!("Could not grow the patch to {} tiles with seed {}", target, seed),
     }
-    return result;
 }
 
-fn add_tile(model: &mut Model, tile: Box<dyn Drawable>) {
+// Place every forced hat
+fn auto_complete(model: &mut Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    let (patch, result) = grow::auto_complete(&patch);
+    set_hats(model, patch);
+    println!("Added {} forced tiles", result.added);
+    for e in &result.dead {
+        println!("No legal tile fits against {:?}", e);
+    }
+}
 
-    let offset = snap_to_edges(&tile, &model.edges.clone(), snap_tolerance(model.scale));
-    tile.append_to_vector(&mut model.tiles, offset.0, offset.1);
+fn write_file(name: &str, contents: &str) {
+    match std::fs::write(name, contents) {
+        Ok(()) => println!("Wrote {}", name),
+        Err(e) => println!("Could not write {}: {}", name, e),
+    }
+}
 
-    let mut new_edges = Vec::new();
-    for t1 in &model.tiles {
-        let mut matches = [false, false, false, false, false, false, false, false, false, false, false, false, false];
-        for t2 in &model.tiles {
-            // @todo don't need to check tile against itself
-            // if (t1 == t2) {
-            //     continue;
-            // }
-            matches = match_edges(&t1, &t2, matches);
-        }
+// Whether the hats can occur in a hat tiling, and if not which one is wrong
+fn check_legality(model: &Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    match legality::check(&patch) {
+        Ok(w) => println!("The {} tiles occur in a level {} {} supertile, moved by {:?}",
+                          patch.len(), w.level, w.kind.name(), w.isometry),
+        Err(i) => println!("Tile {} ({:?}) cannot join the tiles placed before it in any hat tiling",
+                           i, patch.tiles[i]),
+    }
+}
 
-        let e = t1.get_drawable_edges();
-        for i in 0..13 {
-            if !matches[i] {
-                new_edges.push(Edge {center: (e[i].center.0, e[i].center.1),
-                                     angle: e[i].angle,
-                                     length: e[i].length } );
+// Group the hats into supertiles as far as they go
+fn deflate_tiles(model: &Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    match deflation::deflate(&patch) {
+        Ok(d) => {
+            for level in 1..=d.levels() {
+                let names: Vec<&str> = d.at_level(level).iter().map(|&s| d.supertiles[s].kind.name()).collect();
+                println!("Level {}: {}", level, names.join(" "));
             }
-        }
+            println!("{} tiles left over: {:?}", d.leftovers.len(), d.leftovers);
+        },
+        Err(i) => println!("Tile {} ({:?}) cannot be part of any hat tiling", i, patch.tiles[i]),
     }
-    model.edges = new_edges;
 }
 
-fn pop_last_tile(model: &mut Model) {
-
-    model.tiles.pop();
+// Overlaps, holes and partial contacts between the hats
+fn report_validity(model: &Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    let r = validate::validate(&patch);
+    println!("{} boundary and {} internal sides", r.boundary_edges, r.internal_edges);
+    println!("Overlapping tiles: {:?}", r.overlaps);
+    println!("Holes smaller than a tile: {}", r.holes.len());
+    println!("Tiles meeting along part of a side: {:?}", r.partial_contacts);
+    println!("{}", if r.is_valid() { "Valid" } else { "Not valid" });
+}
 
-    let mut new_edges = Vec::new();
-    for t1 in &model.tiles {
-        let mut matches = [false, false, false, false, false, false, false, false, false, false, false, false, false];
-        for t2 in &model.tiles {
-            // @todo don't need to check tile against itself
-            // if (t1 == t2) {
-            //     continue;
-            // }
-            matches = match_edges(&t1, &t2, matches);
-        }
+// Stitch the boundary edges into rings and save them
+fn save_outline(model: &Model) {
+    let pieces = outline::outlines(&model.edges);
+    for (i, o) in pieces.iter().enumerate() {
+        println!("Piece {}: {} corners, {} holes, area {:.3}, perimeter {:.3}",
+                 i, o.outer.len(), o.holes.len(), o.area(), o.perimeter());
+    }
+    let json: Vec<String> = pieces.iter().map(|o| o.to_json()).collect();
+    write_file("outline.json", &format!("[\n  {}\n]\n", json.join(",\n  ")));
+}
 
-        let e = t1.get_drawable_edges();
-        for i in 0..13 {
-            if !matches[i] {
-                new_edges.push(Edge {center: (e[i].center.0, e[i].center.1),
-                                     angle: e[i].angle,
-                                     length: e[i].length } );
+// The coronas around the hat under the mouse
+fn show_coronas(model: &Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    let x = model.current_point.x as f64 / model.scale;
+    let y = model.current_point.y as f64 / model.scale;
+    match patch.tile_at((x, y)) {
+        Some(t) => {
+            let rings = heesch::coronas(&patch, &[t], HEESCH_LIMIT);
+            println!("Tile {} has {} complete coronas", t, rings.len());
+            for (i, r) in rings.iter().enumerate() {
+                println!("Corona {}: {:?}", i + 1, r);
             }
-        }
+        },
+        None => println!("No tile under the mouse"),
+    }
+}
+
+// The Heesch number of the shape made by the hats shown, or of the hat if
+// there are none. When the copies are hats, show the coronas found.
+fn find_heesch(model: &mut Model) {
+    let patch = match hat_patch(model) {
+        Some(p) => p,
+        None => return,
+    };
+    let kites: Vec<_> = patch.tiles.iter().flat_map(|t| t.kites()).collect();
+    let shape = heesch::Polykite::new(&kites).unwrap_or_else(heesch::Polykite::hat);
+    let h = heesch::heesch(&shape, HEESCH_LIMIT);
+    println!("Heesch number {}{}", h.number, if h.number == HEESCH_LIMIT { " or more" } else { "" });
+    let copies: Option<Vec<HatPose>> = std::iter::once(&shape.kites)
+        .chain(h.coronas.iter().flatten())
+        .map(|c| HatPose::from_kites(c))
+        .collect();
+    if let Some(hats) = copies {
+        set_hats(model, Patch::new(hats));
+    }
+}
+
+// Show the next of the clusters of CLUSTER_SIZE hats, most common first
+fn next_cluster(model: &mut Model) {
+    if model.clusters.is_empty() {
+        let patch = Patch::new(metatile::supertile(Metatile::H, 4));
+        model.clusters = cluster::clusters(&patch, CLUSTER_SIZE);
+        model.next_cluster = 0;
+    }
+    if model.clusters.is_empty() {
+        println!("No clusters of {} hats", CLUSTER_SIZE);
+        return;
+    }
+    let c = model.clusters[model.next_cluster].clone();
+    println!("Cluster {} of {}: seen {} times, frequency {:.4}",
+             model.next_cluster + 1, model.clusters.len(), c.count, c.frequency);
+    model.next_cluster = (model.next_cluster + 1) % model.clusters.len();
+    set_hats(model, Patch::new(c.tiles));
+}
+
+// The hierarchy of substitution tiles, otherwise the hats and their seed
+fn save_tiles(model: &Model) {
+    match &model.hierarchy {
+        Some(h) => write_file("hierarchy.json", &h.to_json()),
+        None => match hat_patch(model) {
+            Some(p) => {
+                println!("Patch hash {:016x}", p.stable_hash());
+                write_file("patch.json", &p.with_seed(model.patch_seed).to_json());
+            },
+            None => println!("Only hats can be saved"),
+        },
     }
-    model.edges = new_edges;
+}
+
+fn pop_last_tile(model: &mut Model) {
+
+    model.tiles.pop();
+    model.hierarchy = None;
+    model.patch_seed = None;
+    update_edges(model);
 }
 
 fn event(_app: &App, _model: &mut Model, event: Event) {
@@ -332,6 +497,7 @@ fn view(app: &App, model: &Model, frame: Frame) {
         fill_color2: WHITESMOKE,
         edge_color: SIENNA,
         edge_weight: if model.show_edges { 2. } else { 0. },
+        curved: model.curved,
     };
 
     let drag_props = DrawProps {
@@ -339,6 +505,7 @@ fn view(app: &App, model: &Model, frame: Frame) {
         fill_color2: GAINSBORO,
         edge_color: PINK,
         edge_weight: 0.,
+        curved: false,
     };
 
     let snap_props = DrawProps {
@@ -346,26 +513,32 @@ fn view(app: &App, model: &Model, frame: Frame) {
         fill_color2: LIGHTGREEN,
         edge_color: PINK,
         edge_weight: 0.,
+        curved: false,
     };
 
-    // Draw the tiles
-    for t in &model.tiles {
-        t.draw(&draw, 0., 0., model.scale as f32, &tile_props);
+    let overlap_props = DrawProps {
+        fill_color1: LIGHTCORAL,
+        fill_color2: LIGHTCORAL,
+        edge_color: PINK,
+        edge_weight: 0.,
+        curved: false,
+    };
+
+    // Draw the tiles, as whichever member of the Tile(a,b) family is selected
+    let edges: Vec<_> = model.tiles.iter().map(|t| t.get_drawable_edges()).collect();
+    let outlines = family::deform(&edges, &model.shape);
+    for (t, outline) in model.tiles.iter().zip(outlines.iter()) {
+        t.draw_outline(&draw, outline, 0., 0., model.scale as f32, &tile_props);
     }
 
-    // DEBUGGING: Draw the edges
+    // DEBUGGING: Draw the boundary edges where they really are
     if model.debug {
         for e in &model.edges {
-            let angle_in_radians = e.angle as f64 * std::f64::consts::PI / 180.0f64;
-            let r = (model.scale as f64) * (if e.length == EdgeLength::SHORT { 1.0f64 } else { 1.6f64 });
-            let v = Vector2::<f32>::new((r*angle_in_radians.cos()) as f32,
-                                        (r*angle_in_radians.sin()) as f32);
-            let cpt = pt2((e.center.0 * model.scale + 0.) as f32,
-                          (e.center.1 * model.scale + 0.) as f32);
-            let p1 = cpt - v;
-            let p2 = cpt + v;
-            draw.line().points(p1, p2)
-                .color(BROWN)
+            let (p1, p2) = (e.start.to_point(), e.end.to_point());
+            draw.line()
+                .points(pt2((p1.0 * model.scale) as f32, (p1.1 * model.scale) as f32),
+                        pt2((p2.0 * model.scale) as f32, (p2.1 * model.scale) as f32))
+                .color(if e.length == EdgeLength::Short { BROWN } else { DARKGREEN })
                 .weight(2.);
         }
     }
@@ -373,44 +546,59 @@ fn view(app: &App, model: &Model, frame: Frame) {
     // Draw currently dragged tile
     let x = model.current_point.x as f64 / model.scale;
     let y = model.current_point.y as f64 / model.scale;
-    let tmp = build_tile(&model.next_tile, x, y, model.angle);
-    match tmp {
-        Ok(t) => {
-            let props = if snaps(&model.edges, &t, snap_tolerance(model.scale)) { &snap_props } else { &drag_props };
-            t.draw(&draw, 0., 0., model.scale as f32, props)
-        },
-        Err(_) => println!("Error drawing current tile"),
-    }
+    let t = build_tile(model.next_reflected, x, y, model.rotation);
+    let (placed, snaps) = placed_tile(model, t.as_ref());
+    let props = if overlaps_any(placed.as_ref(), &model.tiles) {
+        &overlap_props
+    } else if snaps {
+        &snap_props
+    } else {
+        &drag_props
+    };
+    let outline = family::deform(&[t.get_drawable_edges()], &model.shape).pop().unwrap_or_default();
+    t.draw_outline(&draw, &outline, 0., 0., model.scale as f32, props);
 
     // Write the result of our drawing to the window's frame.
     draw.to_frame(app, &frame).unwrap();
 }
 
-use nannou::event::*;
-
 fn window_event(_app: &App, model: &mut Model, event: WindowEvent) {
     match event {
         KeyPressed(key) => {
             match key {
-                // Key::Key1 => model.vertex_type = 1,
-                // Key::Key2 => model.vertex_type = 2,
-                // Key::Key3 => model.vertex_type = 3,
-                // Key::Key4 => model.vertex_type = 4,
-                // Key::Key5 => model.vertex_type = 5,
-                // Key::Key6 => model.vertex_type = 6,
-                // Key::Key7 => model.vertex_type = 7,
-                Key::C => { model.tiles = Vec::new(); model.edges = Vec::new(); },
-                Key::E => model.show_edges = !model.show_edges,
-                Key::Up | Key::Down => model.next_tile = match model.next_tile {
-                    tile::Tile::UNREFLECTED => tile::Tile::REFLECTED,
-                    tile::Tile::REFLECTED => tile::Tile::UNREFLECTED,
+                Key::Key1 => load_supertile(model, 1),
+                Key::Key2 => load_supertile(model, 2),
+                Key::Key3 => load_supertile(model, 3),
+                Key::Key4 => load_supertile(model, 4),
+                Key::Key5 => load_supertile(model, 5),
+                Key::C => { model.tiles = Vec::new(); model.edges = Vec::new(); model.hierarchy = None; model.patch_seed = None; },
+                Key::S => save_tiles(model),
+                Key::G => grow_tiles(model),
+                Key::A => auto_complete(model),
+                Key::L => check_legality(model),
+                Key::D => deflate_tiles(model),
+                Key::R => report_validity(model),
+                Key::O => save_outline(model),
+                Key::K => show_coronas(model),
+                Key::H => find_heesch(model),
+                Key::N => next_cluster(model),
+                Key::M => {
+                    model.spectres = !model.spectres;
+                    println!("Generating {}", if model.spectres { "spectres" } else { "hats" });
                 },
+                Key::V => model.curved = !model.curved,
+                Key::E => model.show_edges = !model.show_edges,
+                Key::Up | Key::Down => model.next_reflected = !model.next_reflected,
                 Key::X => model.debug = !model.debug,
+                Key::T => {
+                    model.shape = next_shape(&model.shape);
+                    println!("Tile shape: {}", model.shape.name());
+                },
                 Key::U => pop_last_tile(model),
                 Key::Equals => { model.scale = 2.*model.scale.min(100.) },
                 Key::Minus | Key::Underline => { model.scale = 0.5*model.scale.max(1.) },
-                Key::Left => { model.angle = (model.angle + 30) % 360 },
-                Key::Right => { model.angle = (model.angle + 360 - 30) % 360 },
+                Key::Left => { model.rotation = (model.rotation + 1) % 6 },
+                Key::Right => { model.rotation = (model.rotation + 5) % 6 },
                 _ => println!("KeyPressed = {:?}", key),
             }
         }
@@ -433,11 +621,8 @@ fn window_event(_app: &App, model: &mut Model, event: WindowEvent) {
             //     Ok(t) => for o in t { model.tiles.push(o) },
             //     Err(_) => println!("Error building vertex 2"),
             // }
-            let res = build_tile(&model.next_tile, x, y, model.angle);
-            match res {
-                Ok(t) => add_tile(model, t),
-                Err(_) => println!("Error building vertex 2"),
-            }
+            let t = build_tile(model.next_reflected, x, y, model.rotation);
+            add_tile(model, t);
         }
         MouseReleased(_button) => {}
         MouseEntered => {}
@@ -0,0 +1,389 @@
+// The H, T, P and F metatiles of Smith, Myers, Kaplan and Goodman-Strauss and
+// their substitution rules, after Kaplan's hatviz.
+//
+// Everything is exact on the kite grid: metatile outlines are lattice
+// points and every child, hats included, is placed by an Isometry.
+
+use std::rc::Rc;
+
+use crate::hierarchy::{parse_address, Hierarchy, Supertile};
+use crate::lattice::{eis, hex, Eisenstein, Hex};
+use crate::tile::{HatPose, Isometry};
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Metatile {
+    H,
+    T,
+    P,
+    F,
+}
+
+impl Metatile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metatile::H => "H",
+            Metatile::T => "T",
+            Metatile::P => "P",
+            Metatile::F => "F",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Metatile> {
+        [Metatile::H, Metatile::T, Metatile::P, Metatile::F].iter().copied().find(|m| m.name() == name)
+    }
+}
+
+// Twice the signed area of the triangle 0, u, v, up to a constant factor
+fn cross(u: Eisenstein, v: Eisenstein) -> i64 {
+    u.a*v.b - u.b*v.a
+}
+
+// The lattice point nearest where the lines p1 q1 and p2 q2 cross. Lines
+// through key points of a patch rarely cross on the lattice, so outlines
+// from the next level up are only close to the real metatile shapes. That is
+// enough: outlines only say where to glue metatiles, and every edge glued is
+// built from the same rounded corner, so the hats come out the same. The
+// ignored test no_overlaps_at_every_level checks this up to MAX_LEVEL.
+fn intersect(p1: Eisenstein, q1: Eisenstein, p2: Eisenstein, q2: Eisenstein) -> Eisenstein {
+    let (d1, d2) = (q1 - p1, q2 - p2);
+    let (num, den) = (cross(p2 - p1, d2), cross(d1, d2));
+    let (num, den) = if den < 0 { (-num, -den) } else { (num, den) };
+    let round = |x: i64| (2*x*num + den).div_euclid(2*den);
+    p1 + eis(round(d1.a), round(d1.b))
+}
+
+// The rotation and translation taking a to p and b to q, if there is one
+fn match_two(a: Eisenstein, b: Eisenstein, p: Eisenstein, q: Eisenstein) -> Option<Isometry> {
+    let k = (0..6).find(|&k| (b - a).rotate(k) == q - p)?;
+    Hex::from_center(p - a.rotate(k)).map(|h| Isometry::new(k, false, h))
+}
+
+#[derive(Debug)]
+enum Node {
+    Hat,
+    Meta(Meta),
+}
+
+#[derive(Debug)]
+struct Meta {
+    kind: Metatile,
+    outline: Vec<Eisenstein>,
+    children: Vec<(Isometry, Rc<Node>)>,
+}
+
+impl Meta {
+    fn new(kind: Metatile, outline: Vec<Eisenstein>) -> Meta {
+        Meta { kind, outline, children: Vec::new() }
+    }
+
+    fn add(&mut self, m: Isometry, node: &Rc<Node>) {
+        self.children.push((m, Rc::clone(node)));
+    }
+
+    // Vertex i of child n, in this metatile's coordinates
+    fn eval(&self, n: usize, i: usize) -> Eisenstein {
+        let (m, node) = &self.children[n];
+        m.apply_to_point(outline_of(node)[i])
+    }
+
+    // Move the hexagon nearest the middle of the outline to the origin
+    fn recentre(&mut self) {
+        let n = self.outline.len() as i64;
+        let c = self.outline.iter().fold(Eisenstein::ZERO, |acc, p| acc + *p);
+        let round = |x: i64| (x + 3*n).div_euclid(6*n);
+        let h = hex(round(2*c.a - c.b), round(2*c.b - c.a));
+        for p in self.outline.iter_mut() {
+            *p = *p - h.center();
+        }
+        let m = Isometry::translation(-h);
+        for child in self.children.iter_mut() {
+            child.0 = m.compose(&child.0);
+        }
+    }
+}
+
+fn outline_of(node: &Node) -> &[Eisenstein] {
+    match node {
+        Node::Hat => unreachable!("hats are never matched by outline"),
+        Node::Meta(meta) => &meta.outline,
+    }
+}
+
+// The four level 1 metatiles, as [H, T, P, F], each with its first hat on
+// the origin hexagon
+fn initial() -> [Rc<Node>; 4] {
+    let hat = Rc::new(Node::Hat);
+    let place = |q, r, rotation, reflected| Isometry::new(rotation, reflected, hex(q, r));
+
+    let mut h = Meta::new(Metatile::H, vec![eis(-4, -4), eis(4, -4), eis(6, -2),
+                                            eis(6, 6), eis(4, 6), eis(-4, -2)]);
+    h.add(place(0, 0, 4, false), &hat);
+    h.add(place(2, -1, 4, false), &hat);
+    h.add(place(1, 0, 2, false), &hat);
+    h.add(place(1, -1, 5, true), &hat);
+
+    let mut t = Meta::new(Metatile::T, vec![eis(-2, -2), eis(4, -2), eis(4, 4)]);
+    t.add(place(0, 0, 0, false), &hat);
+
+    let mut p = Meta::new(Metatile::P, vec![eis(-4, -2), eis(4, -2), eis(4, 2), eis(-4, 2)]);
+    p.add(place(0, 0, 0, false), &hat);
+    p.add(place(-1, 1, 5, false), &hat);
+
+    let mut f = Meta::new(Metatile::F, vec![eis(-4, -2), eis(2, -2), eis(4, 0), eis(4, 2), eis(-4, 2)]);
+    f.add(place(0, 0, 0, false), &hat);
+    f.add(place(-1, 1, 5, false), &hat);
+
+    [Rc::new(Node::Meta(h)), Rc::new(Node::Meta(t)), Rc::new(Node::Meta(p)), Rc::new(Node::Meta(f))]
+}
+
+enum Rule {
+    // Attach a metatile to edge e of an existing child
+    Edge(usize, usize, Metatile, usize),
+    // Attach a metatile to the segment from a vertex of one child to a vertex of another
+    Span(usize, usize, usize, usize, Metatile, usize),
+}
+
+use self::Metatile::{F, H, P, T};
+use self::Rule::{Edge, Span};
+
+const RULES: [Rule; 28] = [
+    Edge(0, 0, P, 2), Edge(1, 0, H, 2), Edge(2, 0, P, 2), Edge(3, 0, H, 2),
+    Edge(4, 4, P, 2), Edge(0, 4, F, 3), Edge(2, 4, F, 3), Span(4, 1, 3, 2, F, 0),
+    Edge(8, 3, H, 0), Edge(9, 2, P, 0), Edge(10, 2, H, 0), Edge(11, 4, P, 2),
+    Edge(12, 0, H, 2), Edge(13, 0, F, 3), Edge(14, 2, F, 1), Edge(15, 3, H, 4),
+    Edge(8, 2, F, 1), Edge(17, 3, H, 0), Edge(18, 2, P, 0), Edge(19, 2, H, 2),
+    Edge(20, 4, F, 3), Edge(20, 0, P, 2), Edge(22, 0, H, 2), Edge(23, 4, F, 3),
+    Edge(23, 0, F, 3), Edge(16, 0, P, 2), Span(9, 4, 0, 2, T, 2), Edge(4, 0, F, 3),
+];
+
+// The patch of 29 metatiles from which the next level is cut. None if some
+// metatile would land off the grid, which the rules never ask for.
+fn patch(tiles: &[Rc<Node>; 4]) -> Option<Meta> {
+    let by_kind = |kind: Metatile| &tiles[kind as usize];
+    let mut ret = Meta::new(H, Vec::new());
+    ret.add(Isometry::identity(), by_kind(H));
+    for rule in RULES.iter() {
+        let (p, q, kind, e) = match *rule {
+            Edge(n, i, kind, e) => {
+                let len = outline_of(&ret.children[n].1).len();
+                (ret.eval(n, (i + 1) % len), ret.eval(n, i), kind, e)
+            },
+            Span(n, i, m, j, kind, e) => (ret.eval(m, j), ret.eval(n, i), kind, e),
+        };
+        let node = by_kind(kind);
+        let outline = outline_of(node);
+        let m = match_two(outline[e], outline[(e + 1) % outline.len()], p, q)?;
+        ret.add(m, node);
+    }
+    Some(ret)
+}
+
+// The next level of metatiles, as [H, T, P, F]
+fn substitute(tiles: &[Rc<Node>; 4]) -> Option<[Rc<Node>; 4]> {
+    let patch = patch(tiles)?;
+    let bps1 = patch.eval(8, 2);
+    let bps2 = patch.eval(21, 2);
+    let rbps = bps1 + (bps2 - bps1).rotate(-2);
+
+    let p72 = patch.eval(7, 2);
+    let p252 = patch.eval(25, 2);
+
+    let llc = intersect(bps1, rbps, patch.eval(6, 2), p72);
+    let mut w = patch.eval(6, 2) - llc;
+
+    let mut h_outline = vec![llc, bps1];
+    w = w.rotate(-1);
+    h_outline.push(h_outline[1] + w);
+    h_outline.push(patch.eval(14, 2));
+    w = w.rotate(-1);
+    h_outline.push(h_outline[3] - w);
+    h_outline.push(patch.eval(6, 2));
+
+    let p_outline = vec![p72, p72 + (bps1 - llc), bps1, llc];
+
+    let f_outline = vec![bps2, patch.eval(24, 2), patch.eval(25, 0),
+                         p252, p252 + (llc - bps1)];
+
+    let a = h_outline[2];
+    let b = h_outline[1] + (h_outline[4] - h_outline[5]);
+    let c = b + (a - b).rotate(-1);
+    let t_outline = vec![b, c, a];
+
+    let build = |kind, outline, children: &[usize]| {
+        let mut meta = Meta::new(kind, outline);
+        for &n in children {
+            let (m, node) = &patch.children[n];
+            meta.add(*m, node);
+        }
+        meta.recentre();
+        Rc::new(Node::Meta(meta))
+    };
+    Some([build(H, h_outline, &[0, 9, 16, 27, 26, 6, 1, 8, 10, 15]),
+          build(T, t_outline, &[11]),
+          build(P, p_outline, &[7, 2, 3, 4, 28]),
+          build(F, f_outline, &[21, 20, 22, 23, 24, 25])])
+}
+
+// Supertiles beyond this get slow; level 7 is some 1600 units across and
+// has over 370000 hats
+pub const MAX_LEVEL: u32 = 7;
+
+// Metatiles of the given level, level 1 being the metatiles themselves
+fn metatiles(level: u32) -> Option<[Rc<Node>; 4]> {
+    let mut tiles = initial();
+    for _ in 1..level {
+        tiles = substitute(&tiles)?;
+    }
+    Some(tiles)
+}
+
+// Records a supertile and everything below it, numbering the hats in the
+// order they are reached
+fn collect(meta: &Meta, m: &Isometry, level: u32, parent: Option<usize>, dst: &mut Hierarchy) {
+    let id = dst.supertiles.len();
+    dst.supertiles.push(Supertile { kind: meta.kind, level, parent, children: Vec::new() });
+    if let Some(p) = parent {
+        dst.supertiles[p].children.push(id);
+    }
+    for (cm, child) in &meta.children {
+        let cm = m.compose(cm);
+        match child.as_ref() {
+            Node::Hat => {
+                dst.supertiles[id].children.push(dst.tiles.len());
+                dst.tile_parents.push(id);
+                dst.tiles.push(HatPose::from_isometry(&cm));
+            },
+            Node::Meta(child) => collect(child, &cm, level - 1, Some(id), dst),
+        }
+    }
+}
+
+// The supertile of the given kind cut from metatiles of its level
+fn flatten(tiles: &[Rc<Node>; 4], kind: Metatile, level: u32) -> Hierarchy {
+    let mut h = Hierarchy::default();
+    if let Node::Meta(meta) = tiles[kind as usize].as_ref() {
+        collect(meta, &Isometry::identity(), level, None, &mut h);
+    }
+    h
+}
+
+// Hats of a level n supertile of the given kind. Level 0 is a single hat.
+pub fn supertile(kind: Metatile, level: u32) -> Vec<HatPose> {
+    hierarchy(kind, level).tiles
+}
+
+// A level n supertile of the given kind, keeping every intermediate
+// supertile. Empty if some metatile cannot be glued on the grid, which does
+// not happen at any level this crate uses.
+pub fn hierarchy(kind: Metatile, level: u32) -> Hierarchy {
+    let mut h = Hierarchy::default();
+    if level == 0 {
+        h.tiles.push(HatPose::default());
+        return h;
+    }
+    metatiles(level).map_or(h, |tiles| flatten(&tiles, kind, level))
+}
+
+// Hats of the supertiles of the given kind at levels 1 to MAX_LEVEL in
+// turn, each level substituted from the one before
+pub fn supertiles(kind: Metatile) -> impl Iterator<Item = Vec<HatPose>> {
+    let mut tiles = Some(initial());
+    (1..=MAX_LEVEL).map_while(move |level| {
+        let t = tiles.take()?;
+        tiles = substitute(&t);
+        Some(flatten(&t, kind, level).tiles)
+    })
+}
+
+// The hat at an address together with the supertile context levels above
+// it, cut from an H supertile at the supertile of the right kind nearest its
+// middle. Everything is moved so that the hat has the same pose as in the
+// supertile the address names. Returns the hierarchy and the hat's index,
+// or None if that needs a supertile above MAX_LEVEL.
+pub fn around(address: &str, context: u32) -> Option<(Hierarchy, usize)> {
+    let (kind, path) = parse_address(address)?;
+    let level = path.len() as u32;
+    if level + context > MAX_LEVEL {
+        return None;
+    }
+    let own = hierarchy(kind, level);
+    let t0 = own.tile_at_address(address)?;
+    if context == 0 {
+        return Some((own, t0));
+    }
+
+    let mut big = hierarchy(Metatile::H, level + context);
+    let middle = |s: usize| {
+        let ts = big.tiles_of(s);
+        let (x, y) = ts.iter().map(|&t| big.tiles[t].centroid())
+            .fold((0., 0.), |a, c| (a.0 + c.0, a.1 + c.1));
+        (x / ts.len() as f64, y / ts.len() as f64)
+    };
+    let (cx, cy) = middle(big.root()?);
+    let s = (0..big.supertiles.len())
+        .filter(|&s| big.supertiles[s].kind == kind && big.supertiles[s].level == level)
+        .map(|s| {
+            let (x, y) = middle(s);
+            (s, (x - cx).powi(2) + (y - cy).powi(2))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))?.0;
+    let mut t = s;
+    for &i in &path {
+        t = *big.children(t).get(i)?;
+    }
+
+    let iso = own.tiles[t0].isometry().compose(&big.tiles[t].isometry().inverse());
+    for tile in big.tiles.iter_mut() {
+        *tile = iso.apply_to_pose(tile);
+    }
+    Some((big, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Patch;
+
+    #[test]
+    fn hat_counts() {
+        let counts: Vec<usize> = (1..=5).map(|l| supertile(Metatile::H, l).len()).collect();
+        assert_eq!(counts, [4, 25, 169, 1156, 7921]);
+    }
+
+    #[test]
+    fn no_overlaps() {
+        for kind in [Metatile::H, Metatile::T, Metatile::P, Metatile::F] {
+            for level in 1..=5 {
+                let patch = Patch::new(supertile(kind, level));
+                assert!(patch.overlapping_pairs().is_empty(), "{} level {}", kind.name(), level);
+            }
+        }
+    }
+
+    // The rest of the levels, too slow to run every time
+    #[test]
+    #[ignore]
+    fn no_overlaps_at_every_level() {
+        for kind in [Metatile::H, Metatile::T, Metatile::P, Metatile::F] {
+            for level in 6..=MAX_LEVEL {
+                let patch = Patch::new(supertile(kind, level));
+                assert!(patch.overlapping_pairs().is_empty(), "{} level {}", kind.name(), level);
+            }
+        }
+    }
+
+    #[test]
+    fn around_keeps_the_pose() {
+        let own = hierarchy(Metatile::H, 3);
+        for t in (0..own.tiles.len()).step_by(17) {
+            let address = own.address(t).unwrap();
+            let (big, i) = around(&address, 1).unwrap();
+            assert_eq!(big.tiles[i], own.tiles[t], "{}", address);
+            assert_eq!(big.levels(), 4);
+            assert!(Patch::new(big.tiles).overlapping_pairs().is_empty());
+        }
+        assert!(around("H.0.0.0.0.0.0", 1).is_some());
+        assert!(around("H.0.0.0.0.0.0", 2).is_none());
+        assert!(around("H.0.0.0.0.0.0.0.0", 0).is_none());
+    }
+}
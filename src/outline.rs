@@ -0,0 +1,175 @@
+// The boundary of a patch as closed polygons: an outer ring for each piece
+// of the patch and a ring for each hole in it
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::lattice::{Eisenstein, Vertex};
+use crate::polygon::{self, Point};
+use crate::tile::Edge;
+
+// One piece of a patch. The outer ring runs counter-clockwise and the holes
+// clockwise, so the patch is always on the left. Only corners are kept.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Outline<P = Eisenstein> {
+    pub outer: Vec<P>,
+    pub holes: Vec<Vec<P>>,
+}
+
+fn points<P: Vertex>(ring: &[P]) -> Vec<Point> {
+    ring.iter().map(|p| p.to_point()).collect()
+}
+
+fn perimeter<P: Vertex>(ring: &[P]) -> f64 {
+    let pts = points(ring);
+    let n = pts.len();
+    (0..n).map(|i| {
+        let (p, q) = (pts[i], pts[(i+1) % n]);
+        (q.0 - p.0).hypot(q.1 - p.1)
+    }).sum()
+}
+
+impl<P: Vertex> Outline<P> {
+    // Area covered, holes excluded
+    pub fn area(&self) -> f64 {
+        polygon::area(&points(&self.outer)) + self.holes.iter().map(|h| polygon::area(&points(h))).sum::<f64>()
+    }
+
+    // Length of every ring, holes included
+    pub fn perimeter(&self) -> f64 {
+        perimeter(&self.outer) + self.holes.iter().map(|h| perimeter(h)).sum::<f64>()
+    }
+
+    pub fn to_json(&self) -> String {
+        let ring = |r: &[P]| {
+            let pts: Vec<String> = points(r).iter().map(|p| format!("[{}, {}]", p.0, p.1)).collect();
+            format!("[{}]", pts.join(", "))
+        };
+        let mut s = format!("{{\"outer\": {}, \"holes\": [", ring(&self.outer));
+        for (i, h) in self.holes.iter().enumerate() {
+            let _ = write!(s, "{}{}", if i == 0 { "" } else { ", " }, ring(h));
+        }
+        s.push_str("]}");
+        s
+    }
+}
+
+// Closed loops of boundary edges, each with the patch on its left. Where
+// the boundary touches itself at a vertex, the loops are kept apart.
+pub fn rings<P: Vertex>(edges: &[Edge<P>]) -> Vec<Vec<Edge<P>>> {
+    let mut from: HashMap<P, Vec<usize>> = HashMap::new();
+    for (i, e) in edges.iter().enumerate() {
+        from.entry(e.start).or_default().push(i);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut result = Vec::new();
+    for i in 0..edges.len() {
+        if used[i] {
+            continue;
+        }
+        let mut ring = Vec::new();
+        let mut cur = i;
+        while !used[cur] {
+            used[cur] = true;
+            ring.push(edges[cur]);
+            let e = edges[cur];
+            // The first unused edge clockwise from the way back
+            let back = e.reversed().angle();
+            let next = from.get(&e.end).into_iter().flatten()
+                .copied()
+                .filter(|&j| !used[j])
+                .min_by_key(|&j| (back - edges[j].angle()).rem_euclid(360));
+            match next {
+                Some(j) => cur = j,
+                None => break,
+            }
+        }
+        result.push(ring);
+    }
+    result
+}
+
+// Where a ring changes direction
+fn corners<P: Vertex>(ring: &[Edge<P>]) -> Vec<P> {
+    let n = ring.len();
+    (0..n).filter(|&i| ring[(i + n - 1) % n].angle() != ring[i].angle())
+        .map(|i| ring[i].start)
+        .collect()
+}
+
+// The outline of every piece of a patch, given its boundary edges. Each hole
+// goes with the smallest outer ring around it.
+pub fn outlines<P: Vertex>(edges: &[Edge<P>]) -> Vec<Outline<P>> {
+    let (mut outer, mut holes) = (Vec::new(), Vec::new());
+    for ring in rings(edges) {
+        let c = corners(&ring);
+        if polygon::area(&points(&c)) > 0. {
+            outer.push((ring, c));
+        } else {
+            holes.push((ring, c));
+        }
+    }
+    let mut result: Vec<Outline<P>> = outer.iter()
+        .map(|(_, c)| Outline { outer: c.clone(), holes: Vec::new() })
+        .collect();
+    for (ring, c) in holes {
+        // No two rings share an edge, so the middle of one is never on another
+        let mid = ring[0].center();
+        let around = outer.iter().enumerate()
+            .filter(|(_, (_, o))| polygon::contains(&points(o), mid))
+            .map(|(i, (_, o))| (i, polygon::area(&points(o))))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = around {
+            result[i].holes.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lattice::hex;
+    use crate::metatile::{self, Metatile};
+    use crate::patch::Patch;
+    use crate::tile::HatPose;
+
+    #[test]
+    fn supertile_has_one_ring() {
+        for level in 1..=3 {
+            let patch = Patch::new(metatile::supertile(Metatile::H, level));
+            let outlines = patch.outline();
+            assert_eq!(outlines.len(), 1);
+            assert!(outlines[0].holes.is_empty());
+            assert!((outlines[0].area() - patch.area()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn ring_around_a_hole() {
+        let full = Patch::new(metatile::supertile(Metatile::H, 2));
+        let boundary = full.boundary_edges();
+        let mut patch = full.clone();
+        let inner = patch.tiles.iter()
+            .position(|t| t.get_edges().iter().all(|e| !boundary.contains(e)))
+            .unwrap();
+        let missing = patch.tiles.remove(inner);
+
+        let outlines = patch.outline();
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].holes.len(), 1);
+        assert!((outlines[0].area() - patch.area()).abs() < 1e-6);
+        let hole = -crate::polygon::area(&super::points(&outlines[0].holes[0]));
+        assert!((hole - missing.area()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn touching_at_a_vertex() {
+        let a = HatPose::default();
+        let b = HatPose::new(hex(-1, 0), 3, false);
+        assert!(a.vertices().iter().any(|v| b.vertices().contains(v)));
+        let patch = Patch::new(vec![a, b]);
+        let outlines = patch.outline();
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines.iter().all(|o| o.holes.is_empty() && (o.area() - a.area()).abs() < 1e-9));
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::family::{self, TileShape};
+use crate::lattice::{Hex, hex, Kite};
+use crate::outline::{self, Outline};
+use crate::polygon::{Bounds, Point};
+use crate::tile::{self, Edge, HatPose, Isometry};
+
+// A hat that could be added to a patch, and how many of the patch's boundary
+// edges it would close
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Placement {
+    pub pose: HatPose,
+    pub closed: usize,
+}
+
+// A finite collection of hats
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Patch {
+    pub tiles: Vec<HatPose>,
+    // The seed of the random choices that made the patch, if any
+    pub seed: Option<u64>,
+}
+
+impl Patch {
+    pub fn new(tiles: Vec<HatPose>) -> Self {
+        Self { tiles, seed: None }
+    }
+
+    pub fn with_seed(self, seed: Option<u64>) -> Self {
+        Self { seed, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn transform(&self, iso: &Isometry) -> Patch {
+        Patch::new(self.tiles.iter().map(|t| iso.apply_to_pose(t)).collect()).with_seed(self.seed)
+    }
+
+    // Total area, assuming the tiles do not overlap
+    pub fn area(&self) -> f64 {
+        self.tiles.iter().map(|t| t.area()).sum()
+    }
+
+    pub fn centroid(&self) -> Option<Point> {
+        if self.tiles.is_empty() {
+            return None;
+        }
+        let (mut cx, mut cy, mut a) = (0., 0., 0.);
+        for t in &self.tiles {
+            let ta = t.area();
+            let c = t.centroid();
+            cx += c.0 * ta;
+            cy += c.1 * ta;
+            a += ta;
+        }
+        Some((cx / a, cy / a))
+    }
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.tiles.iter().map(|t| t.bounds()).reduce(|a, b| a.union(&b))
+    }
+
+    // Index of the tile containing p, if any
+    pub fn tile_at(&self, p: Point) -> Option<usize> {
+        self.tiles.iter().position(|t| t.bounds().contains(p) && t.contains(p))
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.tile_at(p).is_some()
+    }
+
+    // Indices of the tiles whose interiors intersect that of pose
+    pub fn overlaps_with(&self, pose: &HatPose) -> Vec<usize> {
+        self.tiles.iter().enumerate()
+            .filter(|(_, t)| t.overlaps(pose))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Whether pose can be added without overlapping any tile
+    pub fn can_place(&self, pose: &HatPose) -> bool {
+        !self.tiles.iter().any(|t| t.overlaps(pose))
+    }
+
+    // Every pair of overlapping tiles, as (i, j) with i < j
+    pub fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        let mut by_hex: HashMap<Hex, Vec<usize>> = HashMap::new();
+        for (i, t) in self.tiles.iter().enumerate() {
+            by_hex.entry(t.hex).or_default().push(i);
+        }
+
+        let mut result = Vec::new();
+        for (i, t) in self.tiles.iter().enumerate() {
+            for dq in -2..=2 {
+                for dr in -2..=2 {
+                    let h = t.hex + hex(dq, dr);
+                    if t.hex.distance(h) > 2 {
+                        continue;
+                    }
+                    for &j in by_hex.get(&h).into_iter().flatten() {
+                        if i < j && t.overlaps(&self.tiles[j]) {
+                            result.push((i, j));
+                        }
+                    }
+                }
+            }
+        }
+        result.sort_unstable();
+        result
+    }
+
+    pub fn has_overlaps(&self) -> bool {
+        !self.overlapping_pairs().is_empty()
+    }
+
+    // Which tiles cover each kite. A kite listed against more than one tile
+    // is an overlap.
+    pub fn kite_map(&self) -> HashMap<Kite, Vec<usize>> {
+        let mut map: HashMap<Kite, Vec<usize>> = HashMap::new();
+        for (i, t) in self.tiles.iter().enumerate() {
+            for k in t.kites().iter() {
+                map.entry(*k).or_default().push(i);
+            }
+        }
+        map
+    }
+
+    // Tile edges not shared with another tile, in tile order
+    pub fn boundary_edges(&self) -> Vec<Edge> {
+        let all: HashSet<Edge> = self.tiles.iter().flat_map(|t| t.get_edges()).collect();
+        self.tiles.iter()
+            .flat_map(|t| t.get_edges())
+            .filter(|e| !all.contains(&e.reversed()))
+            .collect()
+    }
+
+    // The patch as a whole: an outer ring and holes for each piece
+    pub fn outline(&self) -> Vec<Outline> {
+        outline::outlines(&self.boundary_edges())
+    }
+
+    // Every hat, of either chirality, that shares at least one boundary edge
+    // and overlaps no tile, sorted by pose
+    pub fn placements(&self) -> Vec<Placement> {
+        let boundary: HashSet<Edge> = self.boundary_edges().into_iter().collect();
+        let covered: HashSet<_> = self.tiles.iter().flat_map(|t| t.kites().to_vec()).collect();
+
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        for e in &boundary {
+            for reflected in [false, true].iter() {
+                for pose in tile::poses_on_edge(e, *reflected) {
+                    if !seen.insert(pose) {
+                        continue;
+                    }
+                    if pose.kites().iter().any(|k| covered.contains(k)) {
+                        continue;
+                    }
+                    let closed = pose.get_edges().iter()
+                        .filter(|pe| boundary.contains(&pe.reversed()))
+                        .count();
+                    result.push(Placement { pose, closed });
+                }
+            }
+        }
+        result.sort_unstable_by_key(|p| p.pose);
+        result
+    }
+
+    // The same tiles turned, reflected and moved into a standard position:
+    // the least, as a sorted list, of every image with its first tile's
+    // hexagon at the origin. Congruent patches have the same canonical form.
+    pub fn canonical(&self) -> Patch {
+        let mut best: Option<Vec<HatPose>> = None;
+        for reflected in [false, true].iter() {
+            for rotation in 0..6 {
+                let iso = Isometry::new(rotation, *reflected, Hex::default());
+                let mut image: Vec<HatPose> = self.tiles.iter().map(|t| iso.apply_to_pose(t)).collect();
+                image.sort_unstable();
+                let offset = image.first().map_or(Hex::default(), |t| Hex::default() - t.hex);
+                let image: Vec<HatPose> = image.iter().map(|t| t.translate(offset)).collect();
+                if best.iter().all(|b| image < *b) {
+                    best = Some(image);
+                }
+            }
+        }
+        Patch::new(best.unwrap_or_default())
+    }
+
+    pub fn is_congruent(&self, other: &Patch) -> bool {
+        self.len() == other.len() && self.canonical() == other.canonical()
+    }
+
+    // FNV-1a over the canonical form, the same on every platform and build
+    pub fn stable_hash(&self) -> u64 {
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for t in &self.canonical().tiles {
+            for x in [t.hex.q, t.hex.r, t.rotation as i64, t.reflected as i64].iter() {
+                for b in x.to_le_bytes().iter() {
+                    h = (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3);
+                }
+            }
+        }
+        h
+    }
+
+    pub fn to_json(&self) -> String {
+        let seed = self.seed.map_or("null".to_string(), |s| s.to_string());
+        let mut s = format!("{{\n  \"seed\": {},\n  \"tiles\": [", seed);
+        for (i, t) in self.tiles.iter().enumerate() {
+            let _ = write!(s, "{}\n    {{\"hex\": [{}, {}], \"rotation\": {}, \"reflected\": {}}}",
+                           if i == 0 { "" } else { "," },
+                           t.hex.q, t.hex.r, t.rotation, t.reflected);
+        }
+        s.push_str("\n  ]\n}\n");
+        s
+    }
+
+    // Outline of every tile when the patch is drawn as Tile(a, b)
+    pub fn outlines(&self, shape: &TileShape) -> Vec<Vec<Point>> {
+        let edges: Vec<_> = self.tiles.iter().map(|t| t.get_edges()).collect();
+        family::deform(&edges, shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metatile::{self, Metatile};
+
+    #[test]
+    fn placements_of_two_hats() {
+        let a = HatPose::default();
+        let b = tile::attach(&a, 1, false).unwrap()[0];
+        let patch = Patch::new(vec![a, b]);
+        let boundary = patch.boundary_edges().len();
+        assert_eq!(boundary, 22);
+        let placements = patch.placements();
+        assert_eq!(placements.len(), 50);
+        assert_eq!(placements.iter().map(|p| p.closed).max(), Some(6));
+        for p in &placements {
+            assert!(p.closed >= 1);
+            assert!(patch.can_place(&p.pose));
+            let mut bigger = patch.clone();
+            bigger.tiles.push(p.pose);
+            assert_eq!(bigger.boundary_edges().len(), boundary + 14 - 2 * p.closed);
+        }
+    }
+
+    #[test]
+    fn congruent_under_every_isometry() {
+        let patch = Patch::new(metatile::supertile(Metatile::P, 3));
+        for reflected in [false, true] {
+            for rotation in 0..6 {
+                let mut moved = patch.transform(&Isometry::new(rotation, reflected, hex(3 - rotation as i64, 7)));
+                moved.tiles.reverse();
+                moved.tiles.rotate_left(rotation as usize * 5);
+                assert_eq!(moved.canonical(), patch.canonical());
+                assert!(moved.is_congruent(&patch));
+                assert_eq!(moved.stable_hash(), patch.stable_hash());
+            }
+        }
+        let mut smaller = patch.clone();
+        smaller.tiles.pop();
+        assert!(!smaller.is_congruent(&patch));
+        assert_ne!(smaller.stable_hash(), patch.stable_hash());
+        assert_eq!(Patch::default().canonical(), Patch::default());
+    }
+
+    #[test]
+    fn stable_hash_is_fixed() {
+        assert_eq!(Patch::new(metatile::supertile(Metatile::P, 3)).stable_hash(), 0x683f_3234_11ec_105a);
+    }
+}
@@ -0,0 +1,104 @@
+// Measurements on simple polygons given as vertex lists in real coordinates
+
+pub type Point = (f64, f64);
+
+// Axis-aligned bounding box
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    pub fn width(&self) -> f64 {
+        self.max.0 - self.min.0
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.1 - self.min.1
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: (self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            max: (self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        }
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.0 >= self.min.0 && p.0 <= self.max.0 && p.1 >= self.min.1 && p.1 <= self.max.1
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.0 <= other.max.0 && other.min.0 <= self.max.0 &&
+            self.min.1 <= other.max.1 && other.min.1 <= self.max.1
+    }
+}
+
+// Signed area, positive for a counter-clockwise outline
+pub fn area(pts: &[Point]) -> f64 {
+    let n = pts.len();
+    (0..n).map(|i| {
+        let (p, q) = (pts[i], pts[(i+1) % n]);
+        p.0*q.1 - q.0*p.1
+    }).sum::<f64>() / 2.
+}
+
+pub fn centroid(pts: &[Point]) -> Point {
+    let n = pts.len();
+    let a = area(pts);
+    let (mut cx, mut cy) = (0., 0.);
+    for i in 0..n {
+        let (p, q) = (pts[i], pts[(i+1) % n]);
+        let c = p.0*q.1 - q.0*p.1;
+        cx += (p.0 + q.0) * c;
+        cy += (p.1 + q.1) * c;
+    }
+    (cx / (6.*a), cy / (6.*a))
+}
+
+pub fn bounds(pts: &[Point]) -> Bounds {
+    let mut b = Bounds { min: pts[0], max: pts[0] };
+    for p in &pts[1..] {
+        b = b.union(&Bounds { min: *p, max: *p });
+    }
+    b
+}
+
+// Even-odd test. Points exactly on the outline may go either way.
+pub fn contains(pts: &[Point], p: Point) -> bool {
+    let n = pts.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (pts[i], pts[(i+1) % n]);
+        if (a.1 > p.1) != (b.1 > p.1) {
+            let x = a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+            if p.0 < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+// Distance from p to the segment a b
+pub fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let d = (b.0 - a.0, b.1 - a.1);
+    let len2 = d.0*d.0 + d.1*d.1;
+    let t = if len2 == 0. { 0. } else { (((p.0 - a.0)*d.0 + (p.1 - a.1)*d.1) / len2).clamp(0., 1.) };
+    let (x, y) = (a.0 + t*d.0 - p.0, a.1 + t*d.1 - p.1);
+    (x*x + y*y).sqrt()
+}
+
+// Whether the closed segments a b and c d share a point
+pub fn segments_intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let cross = |o: Point, p: Point, q: Point| (p.0 - o.0)*(q.1 - o.1) - (p.1 - o.1)*(q.0 - o.0);
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    if ((d1 > 0. && d2 < 0.) || (d1 < 0. && d2 > 0.)) && ((d3 > 0. && d4 < 0.) || (d3 < 0. && d4 > 0.)) {
+        return true;
+    }
+    // Collinear or touching: some endpoint lies on the other segment
+    let on = |p: Point, q: Point, r: Point| segment_distance(r, p, q) < 1e-9;
+    on(c, d, a) || on(c, d, b) || on(a, b, c) || on(a, b, d)
+}
@@ -0,0 +1,157 @@
+// Covering a region of the plane, given in real coordinates, with hats cut
+// from a substitution supertile
+
+use crate::lattice::Hex;
+use crate::metatile::{self, Metatile};
+use crate::patch::Patch;
+use crate::polygon::{self, Bounds, Point};
+use crate::tile::Isometry;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Region {
+    Rectangle(Bounds),
+    Circle { center: Point, radius: f64 },
+    // A simple polygon, in either orientation
+    Polygon(Vec<Point>),
+}
+
+impl Region {
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            Region::Rectangle(b) => *b,
+            Region::Circle { center, radius } => Bounds {
+                min: (center.0 - radius, center.1 - radius),
+                max: (center.0 + radius, center.1 + radius),
+            },
+            Region::Polygon(pts) => polygon::bounds(pts),
+        }
+    }
+
+    fn outline(&self) -> Option<Vec<Point>> {
+        match self {
+            Region::Rectangle(b) => Some(vec![b.min, (b.max.0, b.min.1), b.max, (b.min.0, b.max.1)]),
+            Region::Circle { .. } => None,
+            Region::Polygon(pts) => Some(pts.clone()),
+        }
+    }
+
+    // Some point of the region
+    fn point(&self) -> Point {
+        match self {
+            Region::Rectangle(b) => b.min,
+            Region::Circle { center, .. } => *center,
+            Region::Polygon(pts) => pts[0],
+        }
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        match self {
+            Region::Rectangle(b) => b.contains(p),
+            Region::Circle { center, radius } => {
+                let (x, y) = (p.0 - center.0, p.1 - center.1);
+                x*x + y*y <= radius*radius
+            },
+            Region::Polygon(pts) => polygon::contains(pts, p),
+        }
+    }
+
+    // Whether the segment p q touches the region
+    pub fn meets_segment(&self, p: Point, q: Point) -> bool {
+        match self {
+            Region::Circle { center, radius } => polygon::segment_distance(*center, p, q) <= *radius,
+            _ => {
+                let pts = self.outline().unwrap_or_default();
+                let n = pts.len();
+                self.contains(p) ||
+                    (0..n).any(|i| polygon::segments_intersect(p, q, pts[i], pts[(i+1) % n]))
+            },
+        }
+    }
+
+    // Whether a polygon, such as a tile outline, touches the region
+    pub fn meets_polygon(&self, pts: &[Point]) -> bool {
+        let n = pts.len();
+        (0..n).any(|i| self.meets_segment(pts[i], pts[(i+1) % n])) || polygon::contains(pts, self.point())
+    }
+}
+
+// Every hat that touches the region, cut from an H supertile large enough
+// to cover it. None if the region is a polygon with fewer than three
+// points, or too big for any supertile up to metatile::MAX_LEVEL.
+pub fn fill(region: &Region) -> Option<Patch> {
+    if let Region::Polygon(pts) = region {
+        if pts.len() < 3 {
+            return None;
+        }
+    }
+    let b = region.bounds();
+    let center = ((b.min.0 + b.max.0) / 2., (b.min.1 + b.max.1) / 2.);
+    for tiles in metatile::supertiles(Metatile::H) {
+        let patch = Patch::new(tiles);
+        let c = patch.centroid()?;
+        let patch = patch.transform(&Isometry::translation(Hex::from_point(center.0 - c.0, center.1 - c.1)));
+        let pb = patch.bounds()?;
+        if !(pb.contains(b.min) && pb.contains(b.max)) {
+            continue;
+        }
+        // The supertile has no holes, so if its outline stays clear of the
+        // region and one point of the region is inside it, all of it is
+        if patch.boundary_edges().iter().any(|e| region.meets_segment(e.start.to_point(), e.end.to_point())) ||
+            !patch.contains(region.point()) {
+            continue;
+        }
+        let tiles = patch.tiles.into_iter()
+            .filter(|t| t.bounds().intersects(&b) && region.meets_polygon(&t.points()))
+            .collect();
+        return Some(Patch::new(tiles));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Points of the region on a grid chosen to miss tile edges
+    fn samples(region: &Region) -> Vec<Point> {
+        let b = region.bounds();
+        let mut result = Vec::new();
+        let mut x = b.min.0 + 0.013;
+        while x < b.max.0 {
+            let mut y = b.min.1 + 0.029;
+            while y < b.max.1 {
+                if region.contains((x, y)) {
+                    result.push((x, y));
+                }
+                y += 0.37;
+            }
+            x += 0.41;
+        }
+        result
+    }
+
+    #[test]
+    fn fills_cover_regions() {
+        let regions = [
+            Region::Rectangle(Bounds { min: (-3., -2.), max: (4., 3.) }),
+            Region::Circle { center: (1., -1.), radius: 3.5 },
+            Region::Polygon(vec![(-2., -2.), (5., 0.), (0., 4.)]),
+        ];
+        for region in regions.iter() {
+            let patch = fill(region).unwrap();
+            let points = samples(region);
+            assert!(points.len() > 50);
+            for p in points {
+                assert!(patch.contains(p), "{:?} misses {:?}", region, p);
+            }
+            assert!(patch.tiles.iter().all(|t| region.meets_polygon(&t.points())));
+            assert!(patch.overlapping_pairs().is_empty());
+        }
+    }
+
+    #[test]
+    fn degenerate_polygons() {
+        assert_eq!(fill(&Region::Polygon(vec![])), None);
+        assert_eq!(fill(&Region::Polygon(vec![(0., 0.), (1., 1.)])), None);
+    }
+}
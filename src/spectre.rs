@@ -0,0 +1,326 @@
+// The spectre: Tile(1, 1) used without reflections, and the Mystic/Spectre
+// substitution of Smith, Myers, Kaplan and Goodman-Strauss, after Kaplan's
+// spectre viewer. Every step is exact on the Cyclotomic lattice.
+
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+use crate::lattice::{Cyclotomic, Vertex};
+use crate::polygon::{self, Point};
+use crate::tile::{edge_index_to_vertex_tuple, Edge, EdgeLength, NUM_EDGES};
+
+// Direction of each edge of the spectre, in multiples of 30 degrees
+const SPECTRE_DIRECTIONS: [i32; NUM_EDGES] = [0, 10, 1, 3, 0, 2, 5, 7, 4, 6, 6, 8, 11, 9];
+
+// Outline of the unreflected spectre, starting at the origin
+fn spectre_geom() -> [Cyclotomic; NUM_EDGES] {
+    let mut pts = [Cyclotomic::ZERO; NUM_EDGES];
+    for i in 1..NUM_EDGES {
+        pts[i] = pts[i-1] + Cyclotomic::ONE.rotate(SPECTRE_DIRECTIONS[i-1]);
+    }
+    pts
+}
+
+// A spectre placed anywhere: mirror in the y axis if reflected, rotate
+// counter-clockwise by a multiple of 30 degrees, then move the origin to
+// origin. Also used as the isometry taking the prototype onto the tile.
+// Substitution only ever produces unreflected spectres.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct SpectrePose {
+    pub origin: Cyclotomic,
+    pub rotation: i32,
+    pub reflected: bool,
+}
+
+impl SpectrePose {
+    pub fn new(origin: Cyclotomic, rotation: i32, reflected: bool) -> Self {
+        Self {
+            origin,
+            rotation: rotation.rem_euclid(12),
+            reflected,
+        }
+    }
+
+    pub fn rotation(steps: i32) -> Self {
+        SpectrePose::new(Cyclotomic::ZERO, steps, false)
+    }
+
+    pub fn translation(offset: Cyclotomic) -> Self {
+        SpectrePose::new(offset, 0, false)
+    }
+
+    pub fn reflection() -> Self {
+        SpectrePose::new(Cyclotomic::ZERO, 0, true)
+    }
+
+    // The isometry that applies other first and then self
+    pub fn compose(&self, other: &SpectrePose) -> SpectrePose {
+        let r = if self.reflected { -other.rotation } else { other.rotation };
+        SpectrePose::new(self.apply_to_point(other.origin),
+                         self.rotation + r,
+                         self.reflected != other.reflected)
+    }
+
+    pub fn apply_to_point(&self, z: Cyclotomic) -> Cyclotomic {
+        let m = if self.reflected { z.mirror() } else { z };
+        self.origin + m.rotate(self.rotation)
+    }
+
+    pub fn translate(&self, offset: Cyclotomic) -> SpectrePose {
+        SpectrePose::translation(offset).compose(self)
+    }
+
+    // Outline in the order of the unreflected prototype
+    pub fn vertices(&self) -> [Cyclotomic; NUM_EDGES] {
+        let mut pts = spectre_geom();
+        for p in pts.iter_mut() {
+            *p = self.apply_to_point(*p);
+        }
+        pts
+    }
+
+    // The straight Tile(1, 1) outline in real coordinates, counter-clockwise
+    pub fn points(&self) -> Vec<Point> {
+        let pts = self.vertices();
+        let order: Vec<usize> = if self.reflected {
+            std::iter::once(0).chain((1..NUM_EDGES).rev()).collect()
+        } else {
+            (0..NUM_EDGES).collect()
+        };
+        order.iter().map(|&i| pts[i].to_point()).collect()
+    }
+
+    pub fn area(&self) -> f64 {
+        polygon::area(&self.points())
+    }
+
+    pub fn centroid(&self) -> Point {
+        polygon::centroid(&self.points())
+    }
+
+    // Edges are numbered 1..=14 as for the hat, edge e running from vertex
+    // e-1 to vertex e of the unreflected tile
+    pub fn edge(&self, e: i32) -> Result<Edge<Cyclotomic>, i32> {
+        let (i1, i2) = edge_index_to_vertex_tuple(e)?;
+        let pts = self.vertices();
+        let (start, end) = if self.reflected { (pts[i2], pts[i1]) } else { (pts[i1], pts[i2]) };
+        Ok(Edge { start, end, length: edge_length(end - start) })
+    }
+
+    pub fn get_edges(&self) -> Vec<Edge<Cyclotomic>> {
+        (1..=NUM_EDGES as i32).filter_map(|i| self.edge(i).ok()).collect()
+    }
+
+    pub fn overlaps(&self, other: &SpectrePose) -> bool {
+        crate::tile::interiors_intersect(&self.get_edges(), &other.get_edges())
+    }
+}
+
+// Spectre edges all have unit length. They are labelled as Tile(1, 1) edges
+// of the hat family are, Short along multiples of 60 degrees and Long in
+// between, so that both sides of an edge agree and the hat's Tile(a, b)
+// scaling leaves spectres unchanged.
+fn edge_length(v: Cyclotomic) -> EdgeLength {
+    if v.angle() % 60 == 0 { EdgeLength::Short } else { EdgeLength::Long }
+}
+
+// An outline with every straight edge replaced by an S-shaped curve. The
+// curve is symmetric about the middle of the edge, so neighbours agree on
+// it, but a mirror image bends the other way, which is what rules out
+// reflected spectres.
+pub fn curved(outline: &[Point], reflected: bool, samples: usize) -> Vec<Point> {
+    let bend = if reflected { -0.15 } else { 0.15 };
+    let n = outline.len();
+    let mut result = Vec::with_capacity(n * samples);
+    for i in 0..n {
+        let (p, q) = (outline[i], outline[(i+1) % n]);
+        let d = (q.0 - p.0, q.1 - p.1);
+        for s in 0..samples {
+            let t = s as f64 / samples as f64;
+            let k = bend * (2. * PI * t).sin();
+            result.push((p.0 + t*d.0 - k*d.1, p.1 + t*d.1 + k*d.0));
+        }
+    }
+    result
+}
+
+// The nine kinds of cluster in the substitution. Gamma is the Mystic, a
+// pair of spectres; the others are single spectres at level 0.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum SpectreKind {
+    Gamma,
+    Delta,
+    Theta,
+    Lambda,
+    Xi,
+    Pi,
+    Sigma,
+    Phi,
+    Psi,
+}
+
+use self::SpectreKind::*;
+
+const KINDS: [SpectreKind; 9] = [Gamma, Delta, Theta, Lambda, Xi, Pi, Sigma, Phi, Psi];
+
+impl SpectreKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gamma => "Gamma",
+            Delta => "Delta",
+            Theta => "Theta",
+            Lambda => "Lambda",
+            Xi => "Xi",
+            Pi => "Pi",
+            Sigma => "Sigma",
+            Phi => "Phi",
+            Psi => "Psi",
+        }
+    }
+}
+
+// The eight places in a supertile and what goes in each, for every kind of
+// supertile
+const SUPER_RULES: [[Option<SpectreKind>; 8]; 9] = [
+    [Some(Pi), Some(Delta), None, Some(Theta), Some(Sigma), Some(Xi), Some(Phi), Some(Gamma)],
+    [Some(Xi), Some(Delta), Some(Xi), Some(Phi), Some(Sigma), Some(Pi), Some(Phi), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Pi), Some(Phi), Some(Sigma), Some(Pi), Some(Phi), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Xi), Some(Phi), Some(Sigma), Some(Pi), Some(Phi), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Pi), Some(Phi), Some(Sigma), Some(Psi), Some(Phi), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Xi), Some(Phi), Some(Sigma), Some(Psi), Some(Phi), Some(Gamma)],
+    [Some(Xi), Some(Delta), Some(Xi), Some(Phi), Some(Sigma), Some(Pi), Some(Lambda), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Psi), Some(Phi), Some(Sigma), Some(Pi), Some(Phi), Some(Gamma)],
+    [Some(Psi), Some(Delta), Some(Psi), Some(Phi), Some(Sigma), Some(Psi), Some(Phi), Some(Gamma)],
+];
+
+// How each place is reached from the last: turn by a multiple of 60
+// degrees, then join key point `from` of the last place to key point `to`
+// of the new one
+const PLACE_RULES: [(i32, usize, usize); 7] = [
+    (1, 3, 1), (0, 2, 0), (1, 3, 1), (1, 3, 1), (0, 2, 0), (1, 3, 1), (-2, 3, 3),
+];
+
+enum Node {
+    Spectre,
+    Cluster(Vec<(SpectrePose, Rc<Node>)>),
+}
+
+// One cluster of each kind, in the order of KINDS, and the four key points
+// they share
+struct System {
+    tiles: Vec<Rc<Node>>,
+    quad: [Cyclotomic; 4],
+}
+
+fn initial() -> System {
+    let geom = spectre_geom();
+    let spectre = Rc::new(Node::Spectre);
+    let mystic = Rc::new(Node::Cluster(vec![
+        (SpectrePose::default(), Rc::clone(&spectre)),
+        (SpectrePose::new(geom[8], 1, false), Rc::clone(&spectre)),
+    ]));
+    let tiles = KINDS.iter()
+        .map(|k| if *k == Gamma { Rc::clone(&mystic) } else { Rc::clone(&spectre) })
+        .collect();
+    System { tiles, quad: [geom[3], geom[5], geom[7], geom[11]] }
+}
+
+fn substitute(sys: &System) -> System {
+    let quad = sys.quad;
+    let mut places = vec![SpectrePose::default()];
+    let mut turn = 0;
+    for &(steps, from, to) in PLACE_RULES.iter() {
+        turn += 2 * steps;
+        let rot = SpectrePose::rotation(turn);
+        let last = places[places.len() - 1];
+        let offset = last.apply_to_point(quad[from]) - rot.apply_to_point(quad[to]);
+        places.push(SpectrePose::translation(offset).compose(&rot));
+    }
+    // Each level is the mirror image of the one before
+    for p in places.iter_mut() {
+        *p = SpectrePose::reflection().compose(p);
+    }
+
+    let tiles = SUPER_RULES.iter().map(|rule| {
+        let children = rule.iter().zip(places.iter())
+            .filter_map(|(kind, place)| kind.map(|k| (*place, Rc::clone(&sys.tiles[k as usize]))))
+            .collect();
+        Rc::new(Node::Cluster(children))
+    }).collect();
+    let quad = [places[6].apply_to_point(quad[2]),
+                places[5].apply_to_point(quad[1]),
+                places[3].apply_to_point(quad[2]),
+                places[0].apply_to_point(quad[1])];
+    System { tiles, quad }
+}
+
+fn collect(node: &Node, pose: &SpectrePose, dst: &mut Vec<SpectrePose>) {
+    match node {
+        Node::Spectre => dst.push(*pose),
+        Node::Cluster(children) => {
+            for (p, child) in children {
+                collect(child, &pose.compose(p), dst);
+            }
+        },
+    }
+}
+
+// Spectres of a level n supertile of the given kind. Level 0 is a single
+// spectre, or the two of the Mystic. Odd levels come out mirrored and are
+// reflected back, so every spectre returned is unreflected.
+pub fn supertile(kind: SpectreKind, level: u32) -> Vec<SpectrePose> {
+    let mut sys = initial();
+    for _ in 0..level {
+        sys = substitute(&sys);
+    }
+    let mut tiles = Vec::new();
+    let flip = if level % 2 == 1 { SpectrePose::reflection() } else { SpectrePose::default() };
+    collect(&sys.tiles[kind as usize], &flip, &mut tiles);
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::outline;
+
+    // Pairs of overlapping spectres, only comparing those close enough to touch
+    fn overlapping_pairs(tiles: &[SpectrePose]) -> Vec<(usize, usize)> {
+        let proto = SpectrePose::default();
+        let c = proto.centroid();
+        let reach = 2. * proto.points().iter().map(|p| (p.0 - c.0).hypot(p.1 - c.1)).fold(0., f64::max);
+        let centroids: Vec<Point> = tiles.iter().map(|t| t.centroid()).collect();
+        let mut result = Vec::new();
+        for i in 0..tiles.len() {
+            for j in i+1..tiles.len() {
+                let (p, q) = (centroids[i], centroids[j]);
+                if (p.0 - q.0).hypot(p.1 - q.1) < reach && tiles[i].overlaps(&tiles[j]) {
+                    result.push((i, j));
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn supertiles() {
+        for kind in KINDS.iter() {
+            let counts = if *kind == Gamma { [2, 8, 62, 488] } else { [1, 9, 71, 559] };
+            for level in 0..=3 {
+                let tiles = supertile(*kind, level);
+                assert_eq!(tiles.len(), counts[level as usize], "{} level {}", kind.name(), level);
+                assert!(tiles.iter().all(|t| !t.reflected));
+                assert!(overlapping_pairs(&tiles).is_empty(), "{} level {}", kind.name(), level);
+
+                let edges: Vec<_> = tiles.iter().flat_map(|t| t.get_edges()).collect();
+                let all: HashSet<_> = edges.iter().cloned().collect();
+                let boundary: Vec<_> = edges.iter().filter(|e| !all.contains(&e.reversed())).cloned().collect();
+                let rings = outline::outlines(&boundary);
+                assert_eq!(rings.len(), 1, "{} level {}", kind.name(), level);
+                assert!(rings[0].holes.is_empty());
+            }
+        }
+    }
+}
@@ -1,450 +1,502 @@
-use crate::geom::{Point2, pt2, Vector2, vec2, Polygon};
+use crate::lattice::{Eisenstein, eis, Hex, hex, Kite, kite, Vertex};
+use crate::polygon::{self, Bounds, Point};
 
-#[derive(PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone)]
 pub enum EdgeLength {
-    SHORT,
-    LONG,
-    DOUBLE // 2 x SHORT
+    Short,
+    Long,
 }
 
-#[derive(Clone)]
-pub struct Edge {
-    pub center: (f64, f64),
-    pub angle: i32,
+impl EdgeLength {
+    // Classify a kite-grid edge vector: a Short edge is half a hexagon side,
+    // a Long edge is an apothem
+    pub fn of(v: Eisenstein) -> Option<EdgeLength> {
+        match v.norm() {
+            1 => Some(EdgeLength::Short),
+            3 => Some(EdgeLength::Long),
+            _ => None,
+        }
+    }
+}
+
+// An edge of a placed tile, running counter-clockwise around it from start to end.
+// Hat edges lie on the kite grid; spectre edges need the finer Cyclotomic points.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone)]
+pub struct Edge<P = Eisenstein> {
+    pub start: P,
+    pub end: P,
     pub length: EdgeLength,
 }
 
-fn edge_index_to_vertex_tuple(e: i32) -> Result<(usize, usize), i32> {
-    match e {
-        1  => Ok(( 0 as usize, 1 as usize)),
-        2  => Ok(( 1 as usize, 2 as usize)),
-        3  => Ok(( 2 as usize, 3 as usize)),
-        4  => Ok(( 3 as usize, 4 as usize)),
-        5  => Ok(( 4 as usize, 5 as usize)),
-        6  => Ok(( 5 as usize, 6 as usize)),
-        7  => Ok(( 6 as usize, 7 as usize)),
-        8  => Ok(( 7 as usize, 8 as usize)),
-        9  => Ok(( 8 as usize, 9 as usize)),
-        10 => Ok(( 9 as usize,10 as usize)),
-        11 => Ok((10 as usize,11 as usize)),
-        12 => Ok((11 as usize,12 as usize)),
-        13 => Ok((12 as usize, 0 as usize)),
-        _ => Err(e),
+impl<P: Vertex> Edge<P> {
+    pub fn center(&self) -> (f64, f64) {
+        let p1 = self.start.to_point();
+        let p2 = self.end.to_point();
+        ( (p1.0+p2.0)/2., (p1.1+p2.1)/2. )
+    }
+
+    pub fn angle(&self) -> i32 {
+        (self.end - self.start).angle()
+    }
+
+    // True if other runs along the same segment in the opposite direction,
+    // i.e. the two tiles sit on either side of this edge
+    pub fn matches(&self, other: &Edge<P>) -> bool {
+        self.start == other.end && self.end == other.start
+    }
+
+    // The same edge as seen from the tile on the other side
+    pub fn reversed(&self) -> Edge<P> {
+        Edge { start: self.end, end: self.start, length: self.length }
+    }
+
+    // The same edge with its endpoints on another lattice
+    pub fn map<Q>(&self, f: impl Fn(P) -> Q) -> Edge<Q> {
+        Edge { start: f(self.start), end: f(self.end), length: self.length }
     }
 }
 
-fn tile_geom() -> [[f64; 2]; 13] {
-    let s3 = 3_f64.sqrt();
-    let r = s3 / 2.;
-
-    [
-        [0.5, 0.],
-        [0.5, r],
-        [-0.25, 3.*r/2.],
-        [-0.5,r],
-        [-1.,r],
-        [-1.,0.],
-        [-1.75,-r/2.],
-        [-1.5,-r],
-        [-0.5,-r],
-        [-0.25,-r/2.],
-        [0.5,-r],
-        [1.25,-r/2.],
-        [1.,0.],
-    ]
+pub const NUM_EDGES: usize = 14;
+
+// Strict point-in-polygon test for a point off the outline. Coordinates are
+// doubled so that edge midpoints stay on the lattice.
+fn strictly_inside<P: Vertex>(edges: &[Edge<P>], p2: P) -> bool {
+    let (px, py) = p2.xy();
+
+    let mut inside = false;
+    for e in edges {
+        let (x1, y1) = (e.start + e.start).xy();
+        let (x2, y2) = (e.end + e.end).xy();
+        if (y1 > py) != (y2 > py) {
+            // px < x1 + (py - y1) * (x2 - x1) / (y2 - y1)
+            let lhs = (px - x1) * (y2 - y1);
+            let rhs = (py - y1) * (x2 - x1);
+            if (y2 > y1 && lhs < rhs) || (y2 < y1 && lhs > rhs) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
 }
 
-pub enum Tile {
-    UNREFLECTED,
-    REFLECTED,
+// True if the interiors of two tiles intersect. Both outlines must be made of
+// single grid edges (kite edges for hats, unit edges for spectres), so that
+// two edges can only meet at their endpoints or coincide.
+pub fn interiors_intersect<P: Vertex>(e1: &[Edge<P>], e2: &[Edge<P>]) -> bool {
+    let one_way = |a: &[Edge<P>], b: &[Edge<P>]| a.iter().any(|e| {
+        if b.iter().any(|o| o.matches(e)) {
+            // Tiles on either side of a shared edge
+            false
+        } else if b.contains(e) {
+            true
+        } else {
+            strictly_inside(b, e.start + e.end)
+        }
+    });
+    one_way(e1, e2) || one_way(e2, e1)
 }
 
-pub struct Unreflected {
-    pub cx: f64,
-    pub cy: f64,
-    pub angle: i32,
+pub(crate) fn edge_index_to_vertex_tuple(e: i32) -> Result<(usize, usize), i32> {
+    match e {
+        1..=14 => Ok(((e-1) as usize, e as usize % NUM_EDGES)),
+        _ => Err(e),
+    }
+}
+
+// Outline of the unreflected hat in lattice coordinates. The origin is the
+// centre of the hexagon that holds four of its eight kites. The long straight
+// side is split at its midpoint, so every edge is a single Short or Long
+// segment of the kite grid.
+const TILE_GEOM: [Eisenstein; NUM_EDGES] = [
+    eis(3, 0),
+    eis(4, 2),
+    eis(3, 3),
+    eis(2, 2),
+    eis(1, 2),
+    eis(0, 0),
+    eis(-2, -1),
+    eis(-2, -2),
+    eis(-1, -2),
+    eis(0, -2),
+    eis(1, -1),
+    eis(2, -2),
+    eis(4, -1),
+    eis(4, 0),
+];
+
+// The eight kites of the unreflected hat at the origin
+const TILE_KITES: [Kite; 8] = [
+    Kite { hex: hex(0, 0), k: 0 },
+    Kite { hex: hex(0, 0), k: 1 },
+    Kite { hex: hex(0, 0), k: 4 },
+    Kite { hex: hex(0, 0), k: 5 },
+    Kite { hex: hex(1, -1), k: 1 },
+    Kite { hex: hex(1, -1), k: 2 },
+    Kite { hex: hex(1, 0), k: 3 },
+    Kite { hex: hex(1, 0), k: 4 },
+];
+
+// A symmetry of the kite grid: optionally mirror in the y axis, then rotate
+// counter-clockwise by a multiple of 60 degrees about the origin, then
+// translate by a whole number of hexagons.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Default)]
+pub struct Isometry {
+    pub rotation: i32,
+    pub reflected: bool,
+    pub translation: Hex,
 }
 
-impl Unreflected {
-    pub fn new(x: f64, y: f64, a: i32) -> Self {
+impl Isometry {
+    pub fn new(rotation: i32, reflected: bool, translation: Hex) -> Self {
         Self {
-            cx: x,
-            cy: y,
-            angle: (a+360)%360
+            rotation: rotation.rem_euclid(6),
+            reflected,
+            translation,
         }
     }
 
-    pub fn rotate(&self, angle: i32) -> Unreflected {
-        Unreflected{ cx: self.cx, cy: self.cy, angle: (self.angle + angle)%360 }
+    pub fn identity() -> Self {
+        Isometry::default()
     }
 
-    pub fn translate(&self, ox: f64, oy: f64) -> Unreflected {
-        Unreflected{ cx: (self.cx + ox), cy: (self.cy + oy), angle: self.angle }
+    pub fn rotation(steps: i32) -> Self {
+        Isometry::new(steps, false, Hex::default())
     }
 
-    pub fn polygon(&self, xoff: f32, yoff: f32, scale: f32) -> Vec<(f32,f32)> {
-        let pts = self.geometry();
+    pub fn translation(offset: Hex) -> Self {
+        Isometry::new(0, false, offset)
+    }
+
+    pub fn reflection() -> Self {
+        Isometry::new(0, true, Hex::default())
+    }
 
-        let xoff64 = xoff as f64;
-        let yoff64 = yoff as f64;
-        let scale64 = scale as f64;
+    // Rotation by steps * 60 degrees about the centre of hexagon h
+    pub fn rotation_about(h: Hex, steps: i32) -> Self {
+        Isometry::translation(h)
+            .compose(&Isometry::rotation(steps))
+            .compose(&Isometry::translation(-h))
+    }
 
-        vec![( (pts[2* 0+0]*scale64 + xoff64) as f32, (pts[2* 0+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 1+0]*scale64 + xoff64) as f32, (pts[2* 1+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 2+0]*scale64 + xoff64) as f32, (pts[2* 2+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 3+0]*scale64 + xoff64) as f32, (pts[2* 3+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 4+0]*scale64 + xoff64) as f32, (pts[2* 4+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 5+0]*scale64 + xoff64) as f32, (pts[2* 5+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 6+0]*scale64 + xoff64) as f32, (pts[2* 6+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 7+0]*scale64 + xoff64) as f32, (pts[2* 7+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 8+0]*scale64 + xoff64) as f32, (pts[2* 8+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 9+0]*scale64 + xoff64) as f32, (pts[2* 9+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*10+0]*scale64 + xoff64) as f32, (pts[2*10+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*11+0]*scale64 + xoff64) as f32, (pts[2*11+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*12+0]*scale64 + xoff64) as f32, (pts[2*12+1]*scale64 + yoff64) as f32 ),
+    // The isometry that applies other first and then self
+    pub fn compose(&self, other: &Isometry) -> Isometry {
+        let r = if self.reflected { -other.rotation } else { other.rotation };
+        Isometry::new(self.rotation + r,
+                      self.reflected != other.reflected,
+                      self.translation + self.apply_to_hex_offset(other.translation))
+    }
 
+    pub fn inverse(&self) -> Isometry {
+        let rotation = if self.reflected { self.rotation } else { -self.rotation };
+        let linear = Isometry::new(rotation, self.reflected, Hex::default());
+        Isometry::new(rotation, self.reflected, -linear.apply_to_hex_offset(self.translation))
+    }
 
-        ]
+    pub fn apply_to_point(&self, z: Eisenstein) -> Eisenstein {
+        let m = if self.reflected { z.mirror() } else { z };
+        self.translation.center() + m.rotate(self.rotation)
     }
 
-    pub fn edge_angle(&self, e: i32) -> Result<i32, i32> {
-        const EA: &'static [i32] = &[90, 150, 240, 180, 270, 210, 300, 0, 60, 330, 30, 120, 180];
+    pub fn apply_to_hex(&self, h: Hex) -> Hex {
+        self.translation + self.apply_to_hex_offset(h)
+    }
 
-        match e {
-            1..=13 => Ok((EA[(e-1) as usize] + self.angle)%360),
-            _ => Err(e),
-        }
+    // Rotate and reflect a displacement between hexagons, ignoring the translation
+    fn apply_to_hex_offset(&self, h: Hex) -> Hex {
+        let m = if self.reflected { h.mirror() } else { h };
+        m.rotate(self.rotation)
     }
 
-    pub fn edge_length(&self, e: i32) -> Result<EdgeLength, i32> {
-        const EL: &'static [EdgeLength] = &[EdgeLength::LONG,
-                                            EdgeLength::LONG,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::LONG,
-                                            EdgeLength::LONG,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::DOUBLE,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::LONG,
-                                            EdgeLength::LONG,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::SHORT];
-        match e {
-            1..=13 => Ok(EL[(e-1) as usize]),
-            _ => Err(e),
-        }
+    pub fn apply_to_kite(&self, kt: Kite) -> Kite {
+        // Mirroring takes direction k * 60 to 180 - k * 60
+        let k = if self.reflected { 3 - kt.k } else { kt.k };
+        kite(self.apply_to_hex(kt.hex), k + self.rotation)
     }
 
-    pub fn edge_center(&self, e: i32) -> Result<(f64, f64), i32> {
-        let (i1, i2) = edge_index_to_vertex_tuple(e)?;
-        let pts = self.geometry();
-        let x1 = pts[2*i1+0];
-        let y1 = pts[2*i1+1];
-        let x2 = pts[2*i2+0];
-        let y2 = pts[2*i2+1];
-        Ok(( (x1+x2)/2., (y1+y2)/2. ))
+    pub fn apply_to_pose(&self, pose: &HatPose) -> HatPose {
+        HatPose::from_isometry(&self.compose(&pose.isometry()))
     }
+}
 
-    pub fn edge_points(&self, e: i32) -> Result<((f64, f64), (f64, f64)), i32> {
-        let (i1, i2) = edge_index_to_vertex_tuple(e)?;
-        let pts = self.geometry();
-        let x1 = pts[2*i1+0];
-        let y1 = pts[2*i1+1];
-        let x2 = pts[2*i2+0];
-        let y2 = pts[2*i2+1];
-        Ok(( (x1,y1), (x2,y2) ))
-    }
-
-    fn geometry(&self) ->  Box<[f64]> {
-        let angle_in_radians = self.angle as f64 * std::f64::consts::PI / 180.;
-        let c = angle_in_radians.cos();
-        let s = angle_in_radians.sin();
-//        let s3 = 3_f64.sqrt();
-//        let r = s3 / 2.;
-
-        let mut boxed_arr = Box::new([0.; 26]);
-        let tg = tile_geom();
-        for i in 0..13 {
-            boxed_arr[2* i+0] = self.cx + c*(tg[ i][0]) - s*(tg[ i][1]);
-            boxed_arr[2* i+1] = self.cy + s*(tg[ i][0]) + c*(tg[ i][1]);
+// A hat placed on the kite grid: the hexagon its origin sits on, a rotation
+// by a multiple of 60 degrees and a reflection. A reflected hat is the mirror
+// image (x -> -x) of the unreflected one, rotated and translated the same way.
+//
+// Edges are numbered 1..=14 on the unreflected tile and keep their number
+// when the tile is reflected, so edge e of either chirality is the same side
+// of the hat. Edge points are always returned in counter-clockwise order.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone, Default)]
+pub struct HatPose {
+    pub hex: Hex,
+    pub rotation: i32,
+    pub reflected: bool,
+}
+
+impl HatPose {
+    pub fn new(hex: Hex, rotation: i32, reflected: bool) -> Self {
+        Self {
+            hex,
+            rotation: rotation.rem_euclid(6),
+            reflected,
         }
+    }
 
-        boxed_arr
+    // The isometry taking the unreflected hat at the origin onto this one
+    pub fn isometry(&self) -> Isometry {
+        Isometry::new(self.rotation, self.reflected, self.hex)
     }
 
-    pub fn get_edges(&self) -> Vec<Edge> {
-        let mut result = Vec::new();
-        for i in 1..14 {
-            let mut e = Edge { center: (0., 0.), angle: 0, length: EdgeLength::SHORT };
-            match self.edge_center(i) {
-                Ok(c) => e.center = c,
-                Err(_) => continue,
-            }
-            match self.edge_angle(i) {
-                Ok(a) => e.angle = a,
-                Err(_) => continue,
-            }
-            match self.edge_length(i) {
-                Ok(l) => e.length = l,
-                Err(_) => continue,
-            }
-            result.push(e);
-        }
-        result
+    pub fn from_isometry(iso: &Isometry) -> HatPose {
+        HatPose::new(iso.translation, iso.rotation, iso.reflected)
     }
 
-}
+    // Rotate in place, about the centre of the tile's own hexagon
+    pub fn rotate(&self, steps: i32) -> HatPose {
+        Isometry::rotation_about(self.hex, steps).apply_to_pose(self)
+    }
 
-pub fn place_unreflected_edge(e: i32, pt: (f64,f64), edge_angle: i32) -> Unreflected {
-    let s5 = 5_f64.sqrt();
-    let phi = (1.+s5)/2.;
-    let h = (5.+2.*s5).sqrt()/2.;
+    pub fn translate(&self, offset: Hex) -> HatPose {
+        Isometry::translation(offset).apply_to_pose(self)
+    }
 
-    match e {
-        1 => {
-            let new_angle = (edge_angle + 360 - 252) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25;
-            let dy = h/2.;
-            Unreflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
-        }
-        2 => {
-            let new_angle = (edge_angle + 144) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25 - phi/2.;
-            let dy = h/2.;
-            Unreflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
-        }
-        3 => {
-            let new_angle = (edge_angle + 36) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25 - phi/2.;
-            let dy = -h/2.;
-            Unreflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
-        }
-        4 => {
-            let new_angle = (edge_angle + 252) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25;
-            let dy = -h/2.;
-            Unreflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
-        }
-        _ => {
-            Unreflected::new(0.,0.,0)
-        }
+    // Swap chirality in place, keeping the hexagon and rotation
+    pub fn reflect(&self) -> HatPose {
+        HatPose::from_isometry(&self.isometry().compose(&Isometry::reflection()))
     }
-}
 
-////////////////////////////////////////////////////////////////////////
+    pub fn polygon(&self, xoff: f32, yoff: f32, scale: f32) -> Vec<(f32,f32)> {
+        self.points().iter()
+            .map(|p| ( (p.0*scale as f64 + xoff as f64) as f32,
+                       (p.1*scale as f64 + yoff as f64) as f32 ))
+            .collect()
+    }
 
+    // Outline in real coordinates, counter-clockwise
+    pub fn points(&self) -> Vec<Point> {
+        let pts = self.vertices();
 
-pub struct Reflected {
-    pub cx: f64,
-    pub cy: f64,
-    pub angle: i32,
-}
+        // Reflecting reverses the winding, so walk the outline backwards
+        let order: Vec<usize> = if self.reflected {
+            std::iter::once(0).chain((1..NUM_EDGES).rev()).collect()
+        } else {
+            (0..NUM_EDGES).collect()
+        };
 
-impl Reflected {
-    pub fn new(x: f64, y: f64, a: i32) -> Self {
-        Self {
-            cx: x,
-            cy: y,
-            angle: (a+360)%360
-        }
+        order.iter().map(|&i| pts[i].to_point()).collect()
     }
 
-    pub fn rotate(&self, angle: i32) -> Reflected {
-        Reflected{ cx: self.cx, cy: self.cy, angle: (self.angle + angle)%360 }
+    pub fn area(&self) -> f64 {
+        polygon::area(&self.points())
     }
 
-    pub fn translate(&self, ox: f64, oy: f64) -> Reflected {
-        Reflected{ cx: (self.cx + ox), cy: (self.cy + oy), angle: self.angle }
+    pub fn centroid(&self) -> Point {
+        polygon::centroid(&self.points())
     }
 
-    pub fn polygon(&self, xoff: f32, yoff: f32, scale: f32) -> Vec<(f32,f32)> {
-        let pts = self.geometry();
+    pub fn bounds(&self) -> Bounds {
+        polygon::bounds(&self.points())
+    }
 
-        let xoff64 = xoff as f64;
-        let yoff64 = yoff as f64;
-        let scale64 = scale as f64;
+    pub fn contains(&self, p: Point) -> bool {
+        polygon::contains(&self.points(), p)
+    }
 
-        vec![( (pts[2* 0+0]*scale64 + xoff64) as f32, (pts[2* 0+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*12+0]*scale64 + xoff64) as f32, (pts[2*12+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*11+0]*scale64 + xoff64) as f32, (pts[2*11+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2*10+0]*scale64 + xoff64) as f32, (pts[2*10+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 9+0]*scale64 + xoff64) as f32, (pts[2* 9+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 8+0]*scale64 + xoff64) as f32, (pts[2* 8+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 7+0]*scale64 + xoff64) as f32, (pts[2* 7+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 6+0]*scale64 + xoff64) as f32, (pts[2* 6+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 5+0]*scale64 + xoff64) as f32, (pts[2* 5+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 4+0]*scale64 + xoff64) as f32, (pts[2* 4+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 3+0]*scale64 + xoff64) as f32, (pts[2* 3+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 2+0]*scale64 + xoff64) as f32, (pts[2* 2+1]*scale64 + yoff64) as f32 ),
-             ( (pts[2* 1+0]*scale64 + xoff64) as f32, (pts[2* 1+1]*scale64 + yoff64) as f32 ),
+    pub fn edge_angle(&self, e: i32) -> Result<i32, i32> {
+        Ok(self.edge(e)?.angle())
+    }
 
+    pub fn edge_length(&self, e: i32) -> Result<EdgeLength, i32> {
+        let (i1, i2) = edge_index_to_vertex_tuple(e)?;
+        EdgeLength::of(TILE_GEOM[i2] - TILE_GEOM[i1]).ok_or(e)
+    }
 
-        ]
+    pub fn edge_center(&self, e: i32) -> Result<(f64, f64), i32> {
+        Ok(self.edge(e)?.center())
     }
 
-    pub fn edge_angle(&self, e: i32) -> Result<i32, i32> {
-        const EA: &'static [i32] = &[180, 240, 330, 30, 300, 360, 60, 150, 90, 180, 120, 210, 270];
+    pub fn edge_points(&self, e: i32) -> Result<(Eisenstein, Eisenstein), i32> {
+        let (i1, i2) = edge_index_to_vertex_tuple(e)?;
+        let pts = self.vertices();
+        if self.reflected {
+            Ok((pts[i2], pts[i1]))
+        } else {
+            Ok((pts[i1], pts[i2]))
+        }
+    }
+
+    pub fn edge(&self, e: i32) -> Result<Edge, i32> {
+        let (start, end) = self.edge_points(e)?;
+        Ok(Edge { start, end, length: self.edge_length(e)? })
+    }
 
-        match e {
-            1..=13 => Ok((EA[(e-1) as usize] + self.angle)%360),
-            _ => Err(e),
+    // Outline in the order of the unreflected prototype
+    pub fn vertices(&self) -> [Eisenstein; NUM_EDGES] {
+        let iso = self.isometry();
+        let mut pts = [Eisenstein::ZERO; NUM_EDGES];
+        for (p, g) in pts.iter_mut().zip(TILE_GEOM.iter()) {
+            *p = iso.apply_to_point(*g);
         }
+        pts
     }
 
-    pub fn edge_length(&self, e: i32) -> Result<EdgeLength, i32> {
-        const EL: &'static [EdgeLength] = &[EdgeLength::SHORT,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::LONG,
-                                            EdgeLength::LONG,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::DOUBLE,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::LONG,
-                                            EdgeLength::LONG,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::SHORT,
-                                            EdgeLength::LONG,
-                                            EdgeLength::LONG];
-        match e {
-            1..=13 => Ok(EL[(e-1) as usize]),
-            _ => Err(e),
+    pub fn kites(&self) -> [Kite; 8] {
+        let iso = self.isometry();
+        let mut kites = [Kite::default(); 8];
+        for (k, t) in kites.iter_mut().zip(TILE_KITES.iter()) {
+            *k = iso.apply_to_kite(*t);
         }
+        kites
     }
 
-    pub fn edge_center(&self, e: i32) -> Result<(f64, f64), i32> {
-        let (i1, i2) = edge_index_to_vertex_tuple(14 - e)?;
-        let pts = self.geometry();
-        let x1 = pts[2*i1+0];
-        let y1 = pts[2*i1+1];
-        let x2 = pts[2*i2+0];
-        let y2 = pts[2*i2+1];
-        Ok(( (x1+x2)/2., (y1+y2)/2. ))
-    }
-
-    pub fn edge_points(&self, e: i32) -> Result<((f64, f64), (f64, f64)), i32> {
-        let (i1, i2) = edge_index_to_vertex_tuple(14 - e)?;
-        let pts = self.geometry();
-        let x1 = pts[2*i1+0];
-        let y1 = pts[2*i1+1];
-        let x2 = pts[2*i2+0];
-        let y2 = pts[2*i2+1];
-        Ok(( (x1,y1), (x2,y2) ))
-    }
-
-    fn geometry(&self) ->  Box<[f64]> {
-        let angle_in_radians = self.angle as f64 * std::f64::consts::PI / 180.;
-        let c = angle_in_radians.cos();
-        let s = angle_in_radians.sin();
-//        let s3 = 3_f64.sqrt();
-//        let r = s3 / 2.;
-
-        let mut boxed_arr = Box::new([0.; 26]);
-        let tg = tile_geom();
-        for i in 0..13 {
-            boxed_arr[2* i+0] = self.cx + c*(-tg[ i][0]) - s*(tg[ i][1]);
-            boxed_arr[2* i+1] = self.cy + s*(-tg[ i][0]) + c*(tg[ i][1]);
+    // The hat made up of exactly these kites, in any order, if there is one
+    pub fn from_kites(kites: &[Kite]) -> Option<HatPose> {
+        if kites.len() != 8 {
+            return None;
+        }
+        let mut wanted = kites.to_vec();
+        wanted.sort_unstable();
+
+        // A hat has four kites in its own hexagon and two in each of two others
+        let main = kites.iter()
+            .map(|k| k.hex)
+            .find(|h| kites.iter().filter(|k| k.hex == *h).count() == 4)?;
+
+        for reflected in [false, true].iter() {
+            for rotation in 0..6 {
+                let pose = HatPose::new(main, rotation, *reflected);
+                let mut found = pose.kites().to_vec();
+                found.sort_unstable();
+                if found == wanted {
+                    return Some(pose);
+                }
+            }
         }
+        None
+    }
 
-        boxed_arr
+    pub fn overlaps(&self, other: &HatPose) -> bool {
+        // Every kite of a hat lies in its own hexagon or a neighbouring one
+        if self.hex.distance(other.hex) > 2 {
+            return false;
+        }
+        interiors_intersect(&self.get_edges(), &other.get_edges())
     }
 
+    // Same as edge(1..=14), working out the vertices only once
     pub fn get_edges(&self) -> Vec<Edge> {
-        let mut result = Vec::new();
-        for i in 1..14 {
-            let mut e = Edge { center: (0., 0.), angle: 0, length: EdgeLength::SHORT };
-            match self.edge_center(i) {
-                Ok(c) => e.center = c,
-                Err(_) => continue,
+        let pts = self.vertices();
+        (1..=NUM_EDGES as i32).filter_map(|e| {
+            let (i1, i2) = edge_index_to_vertex_tuple(e).ok()?;
+            let length = self.edge_length(e).ok()?;
+            if self.reflected {
+                Some(Edge { start: pts[i2], end: pts[i1], length })
+            } else {
+                Some(Edge { start: pts[i1], end: pts[i2], length })
             }
-            match self.edge_angle(i) {
-                Ok(a) => e.angle = a,
-                Err(_) => continue,
+        }).collect()
+    }
+
+}
+
+// Every hat of the given chirality with an edge running along edge in the
+// opposite direction, i.e. every way of placing a hat on the far side of it
+pub fn poses_on_edge(edge: &Edge, reflected: bool) -> Vec<HatPose> {
+    let mut result = Vec::new();
+    for rotation in 0..6 {
+        let proto = HatPose::new(Hex::default(), rotation, reflected);
+        for pe in proto.get_edges() {
+            if pe.length != edge.length || pe.end - pe.start != edge.start - edge.end {
+                continue;
             }
-            match self.edge_length(i) {
-                Ok(l) => e.length = l,
-                Err(_) => continue,
+            if let Some(offset) = Hex::from_center(edge.start - pe.end) {
+                result.push(proto.translate(offset));
             }
-            result.push(e);
         }
-        result
     }
+    result
+}
 
+// Every hat of the given chirality that shares edge e of pose exactly without
+// overlapping it
+pub fn attach(pose: &HatPose, e: i32, reflected: bool) -> Result<Vec<HatPose>, i32> {
+    let edge = pose.edge(e)?;
+    Ok(poses_on_edge(&edge, reflected).into_iter()
+       .filter(|p| !p.overlaps(pose))
+       .collect())
 }
 
-pub fn place_reflected_edge(e: i32, pt: (f64,f64), edge_angle: i32) -> Reflected {
-    let s5 = 5_f64.sqrt();
-    let phi = (1.+s5)/2.;
-    let h = (5.+2.*s5).sqrt()/2.;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    match e {
-        1 => {
-            let new_angle = (edge_angle + 360 - 252) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25;
-            let dy = h/2.;
-            Reflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
-        }
-        2 => {
-            let new_angle = (edge_angle + 144) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25 - phi/2.;
-            let dy = h/2.;
-            Reflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
+    fn isometries() -> Vec<Isometry> {
+        [false, true].iter()
+            .flat_map(|&r| (0..6).map(move |k| Isometry::new(k, r, hex(k as i64 - 2, 1 - k as i64))))
+            .collect()
+    }
+
+    #[test]
+    fn compose_and_inverse() {
+        let z = eis(3, -1);
+        for a in isometries() {
+            assert_eq!(a.compose(&a.inverse()), Isometry::identity());
+            assert_eq!(a.inverse().compose(&a), Isometry::identity());
+            assert_eq!(a.inverse().apply_to_point(a.apply_to_point(z)), z);
+            for b in isometries() {
+                let ab = a.compose(&b);
+                assert_eq!(ab.apply_to_point(z), a.apply_to_point(b.apply_to_point(z)));
+                assert_eq!(ab.inverse(), b.inverse().compose(&a.inverse()));
+            }
         }
-        3 => {
-            let new_angle = (edge_angle + 36) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25 - phi/2.;
-            let dy = -h/2.;
-            Reflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
+        let turn = Isometry::rotation_about(hex(1, 2), 1);
+        assert_eq!(turn.apply_to_hex(hex(1, 2)), hex(1, 2));
+    }
+
+    #[test]
+    fn poses_follow_isometries() {
+        let pose = HatPose::new(hex(2, -1), 4, true);
+        for a in isometries() {
+            let moved = a.apply_to_pose(&pose);
+            assert_eq!(moved.vertices().to_vec(), pose.vertices().iter().map(|v| a.apply_to_point(*v)).collect::<Vec<_>>());
+            assert_eq!(HatPose::from_isometry(&moved.isometry()), moved);
         }
-        4 => {
-            let new_angle = (edge_angle + 252) % 360;
-            let c = ((new_angle as f64) * std::f64::consts::PI / 180.).cos();
-            let s = ((new_angle as f64) * std::f64::consts::PI / 180.).sin();
-
-            let dx = 0.25;
-            let dy = -h/2.;
-            Reflected::new(pt.0 + dx*c - dy*s,
-                      pt.1 + dx*s + dy*c,
-                      new_angle)
+    }
+
+    #[test]
+    fn kites_round_trip() {
+        for reflected in [false, true] {
+            for rotation in 0..6 {
+                let pose = HatPose::new(hex(-1, 3), rotation, reflected);
+                let mut kites = pose.kites().to_vec();
+                assert_eq!(HatPose::from_kites(&kites), Some(pose));
+                kites.reverse();
+                assert_eq!(HatPose::from_kites(&kites), Some(pose));
+                kites.pop();
+                assert_eq!(HatPose::from_kites(&kites), None);
+            }
         }
-        _ => {
-            Reflected::new(0.,0.,0)
+    }
+
+    #[test]
+    fn attached_hats_share_the_edge() {
+        let pose = HatPose::new(hex(1, 1), 2, false);
+        for e in 1..=NUM_EDGES as i32 {
+            let edge = pose.edge(e).unwrap();
+            let mut found = 0;
+            for reflected in [false, true] {
+                for other in attach(&pose, e, reflected).unwrap() {
+                    assert_eq!(other.reflected, reflected);
+                    assert!(other.get_edges().contains(&edge.reversed()));
+                    assert!(!other.overlaps(&pose));
+                    found += 1;
+                }
+                for other in poses_on_edge(&edge, reflected) {
+                    assert!(other.get_edges().iter().any(|o| o.matches(&edge)));
+                }
+            }
+            assert!(found > 0, "edge {}", e);
         }
+        assert_eq!(attach(&pose, 0, false), Err(0));
+        assert_eq!(attach(&pose, 15, false), Err(15));
     }
 }
-
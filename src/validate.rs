@@ -0,0 +1,168 @@
+// Checks on a patch of hats: overlaps, small holes, and sides that only
+// partly meet.
+//
+// Sides are the hat's 13 straight sides, the long one made of two kite-grid
+// edges. Two tiles meet edge to edge when a side of one is exactly a side of
+// the other. Hat tilings are not edge to edge in this sense: the long side
+// of a hat often meets two others. So partial contacts are reported, but do
+// not make a patch invalid.
+
+use std::collections::HashMap;
+
+use crate::lattice::Eisenstein;
+use crate::outline;
+use crate::patch::Patch;
+use crate::polygon;
+use crate::tile::{Edge, HatPose};
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Report {
+    // Pairs of tiles whose interiors intersect, as (i, j) with i < j
+    pub overlaps: Vec<(usize, usize)>,
+    // Outlines of enclosed gaps smaller than a hat, clockwise
+    pub holes: Vec<Vec<Eisenstein>>,
+    // Pairs of tiles with a side that only partly meets the other's, as
+    // (i, j) with i < j
+    pub partial_contacts: Vec<(usize, usize)>,
+    // Sides meeting nothing, and pairs of sides meeting exactly
+    pub boundary_edges: usize,
+    pub internal_edges: usize,
+}
+
+impl Report {
+    // No overlaps and no gaps too small to fill
+    pub fn is_valid(&self) -> bool {
+        self.overlaps.is_empty() && self.holes.is_empty()
+    }
+}
+
+// A tile's edges, counter-clockwise, grouped into straight sides
+fn sides(pose: &HatPose) -> Vec<Vec<Edge>> {
+    let edges = pose.get_edges();
+    let straight = |a: &Edge, b: &Edge| a.end - a.start == b.end - b.start;
+    // Start at a corner, so that no side wraps around
+    let n = edges.len();
+    let first = (0..n).find(|&i| !straight(&edges[(i + n - 1) % n], &edges[i])).unwrap_or(0);
+    let mut result: Vec<Vec<Edge>> = Vec::new();
+    for i in 0..n {
+        let e = edges[(first + i) % n];
+        match result.last_mut() {
+            Some(side) if straight(&side[side.len() - 1], &e) => side.push(e),
+            _ => result.push(vec![e]),
+        }
+    }
+    result
+}
+
+pub fn validate(patch: &Patch) -> Report {
+    let mut report = Report { overlaps: patch.overlapping_pairs(), ..Report::default() };
+
+    let sides: Vec<Vec<Vec<Edge>>> = patch.tiles.iter().map(sides).collect();
+    let mut owner: HashMap<Edge, (usize, usize)> = HashMap::new();
+    for (i, ts) in sides.iter().enumerate() {
+        for (s, side) in ts.iter().enumerate() {
+            for e in side {
+                owner.insert(*e, (i, s));
+            }
+        }
+    }
+    for (i, ts) in sides.iter().enumerate() {
+        for side in ts {
+            let others: Vec<Option<(usize, usize)>> = side.iter().map(|e| owner.get(&e.reversed()).copied()).collect();
+            if others.iter().all(|o| o.is_none()) {
+                report.boundary_edges += 1;
+                continue;
+            }
+            let exact = match others[0] {
+                Some((j, t)) => others.iter().all(|o| *o == Some((j, t))) && sides[j][t].len() == side.len(),
+                None => false,
+            };
+            if exact {
+                if others[0].is_some_and(|(j, _)| i < j) {
+                    report.internal_edges += 1;
+                }
+                continue;
+            }
+            for &(j, _) in others.iter().flatten() {
+                report.partial_contacts.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    report.partial_contacts.sort_unstable();
+    report.partial_contacts.dedup();
+
+    let hat = HatPose::default().area();
+    for ring in outline::rings(&patch.boundary_edges()) {
+        let pts: Vec<Eisenstein> = ring.iter().map(|e| e.start).collect();
+        let area = polygon::area(&pts.iter().map(|p| p.to_point()).collect::<Vec<_>>());
+        if area < 0. && -area < hat - 1e-9 {
+            report.holes.push(pts);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lattice::hex;
+    use crate::metatile::{self, Metatile};
+    use crate::tile;
+
+    #[test]
+    fn overlapping_pair() {
+        let patch = Patch::new(vec![HatPose::default(), HatPose::new(hex(0, 0), 1, false)]);
+        let report = validate(&patch);
+        assert_eq!(report.overlaps, vec![(0, 1)]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn small_holes() {
+        // Two hats taken out of a supertile and one put back out of place
+        // leave two gaps that together make up a hat
+        let mut tiles = metatile::supertile(Metatile::H, 2);
+        let (a, b) = (tiles[1], tiles[4]);
+        tiles.retain(|t| *t != a && *t != b);
+        tiles.push(HatPose::new(hex(0, -1), 1, false));
+        let report = validate(&Patch::new(tiles));
+        assert!(report.overlaps.is_empty());
+        assert_eq!(report.holes.len(), 2);
+        assert!(!report.is_valid());
+
+        let hat = HatPose::default().area();
+        let areas: Vec<f64> = report.holes.iter()
+            .map(|h| polygon::area(&h.iter().map(|p| p.to_point()).collect::<Vec<_>>()))
+            .collect();
+        assert!(areas.iter().all(|a| *a < 0. && -a < hat));
+        assert!((areas.iter().sum::<f64>() + hat).abs() < 1e-9);
+    }
+
+    #[test]
+    fn partial_contact() {
+        let patch = Patch::new(vec![HatPose::default(), HatPose::new(hex(-1, 0), 2, false)]);
+        let report = validate(&patch);
+        assert_eq!(report.partial_contacts, vec![(0, 1)]);
+        assert_eq!(report.internal_edges, 0);
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn side_counts() {
+        // Edge to edge, every side is on the boundary or shared with one other
+        let a = HatPose::default();
+        let b = tile::attach(&a, 1, false).unwrap()[0];
+        let report = validate(&Patch::new(vec![a, b]));
+        assert!(report.partial_contacts.is_empty());
+        assert_eq!(report.boundary_edges + 2 * report.internal_edges, 2 * 13);
+
+        // In a supertile some sides only partly meet others, and are
+        // counted as neither
+        let patch = Patch::new(metatile::supertile(Metatile::H, 2));
+        let report = validate(&patch);
+        assert!(report.is_valid());
+        assert!(!report.partial_contacts.is_empty());
+        assert!(report.boundary_edges + 2 * report.internal_edges < 13 * patch.len());
+        assert_eq!((report.boundary_edges, report.internal_edges), (69, 111));
+    }
+}